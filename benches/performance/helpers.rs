@@ -1,57 +1,9 @@
-use compact_map::CompactMap;
+use compact_map::MapLikeMut;
 use rand::distributions::{Distribution, Standard};
 use rand::prelude::IteratorRandom;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::hash::Hash;
 
-pub trait Map<K, V>
-where
-    K: Eq + Hash,
-{
-    fn len(&self) -> usize;
-    fn insert(&mut self, key: K, value: V) -> Option<V>;
-    fn get(&self, key: &K) -> Option<&V>;
-    fn remove(&mut self, key: &K) -> Option<V>;
-}
-
-impl<K: Eq + Hash, V> Map<K, V> for HashMap<K, V> {
-    #[inline(always)]
-    fn len(&self) -> usize {
-        self.len()
-    }
-    #[inline(always)]
-    fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.insert(key, value)
-    }
-    #[inline(always)]
-    fn get(&self, key: &K) -> Option<&V> {
-        self.get(key)
-    }
-    #[inline(always)]
-    fn remove(&mut self, key: &K) -> Option<V> {
-        self.remove(key)
-    }
-}
-
-impl<K: Eq + Hash, V, const N: usize> Map<K, V> for CompactMap<K, V, N> {
-    #[inline(always)]
-    fn len(&self) -> usize {
-        self.len()
-    }
-    #[inline(always)]
-    fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.insert(key, value)
-    }
-    #[inline(always)]
-    fn get(&self, key: &K) -> Option<&V> {
-        self.get(key)
-    }
-    #[inline(always)]
-    fn remove(&mut self, key: &K) -> Option<V> {
-        self.remove(key)
-    }
-}
-
 #[derive(Clone)]
 pub struct RandomTest<R, M, K, V> {
     rng: R,
@@ -65,7 +17,7 @@ impl<R, K, V, M> RandomTest<R, M, K, V>
 where
     R: rand::Rng,
     K: Eq + Hash + Clone,
-    M: Map<K, V>,
+    M: MapLikeMut<K, V>,
     Standard: Distribution<K>,
     Standard: Distribution<V>,
 {
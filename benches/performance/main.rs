@@ -3,7 +3,6 @@ use compact_map::CompactMap;
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
 use rand::SeedableRng;
 use std::collections::HashMap;
-use std::hash::Hash;
 
 mod helpers;
 
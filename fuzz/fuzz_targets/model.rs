@@ -0,0 +1,20 @@
+//! Applies the operations decoded from the input to a `CompactMap` and a `HashMap`,
+//! and checks that they agree after every step.
+#![no_main]
+
+#[path = "../../tests/model/ops.rs"]
+mod ops;
+
+use compact_map::CompactMap;
+use libfuzzer_sys::fuzz_target;
+use ops::{apply, assert_same, Op};
+use std::collections::HashMap;
+
+fuzz_target!(|ops: Vec<Op>| {
+    let mut map = CompactMap::new();
+    let mut model = HashMap::new();
+    for op in ops {
+        apply(&mut map, &mut model, op);
+        assert_same(&map, &model);
+    }
+});
@@ -0,0 +1,22 @@
+use crate::CompactMap;
+use ::arbitrary::{size_hint, Arbitrary, Result, Unstructured};
+use std::hash::Hash;
+
+impl<'a, K, V, const N: usize> Arbitrary<'a> for CompactMap<K, V, N>
+where
+    K: Arbitrary<'a> + Eq + Hash,
+    V: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+
+    #[inline]
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        size_hint::and(<usize as Arbitrary>::size_hint(depth), (0, None))
+    }
+}
@@ -1,31 +1,63 @@
 use crate::base::{
     drain::{DrainInner, HeaplessDrain},
-    entry::{Entry, HeaplessEntry, OccupiedEntry, VacantEntry},
+    entry::{
+        Entry, HeaplessEntry, OccupiedEntry, OccupiedEntryInner, VacantEntry, VacantEntryInner,
+    },
+    inline_vec::InlineVec,
     iter::{IntoIterInner, IterInner, IterMutInner},
+    lookup::Lookup,
+    spilled::{Allocator, Global, HashMap, SpilledBox},
 };
+use crate::utils::{checked_assert, unreachable_unchecked};
+use std::alloc::Layout;
+#[cfg(feature = "many_mut")]
 use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::fmt::Display;
-use std::hash::Hash;
-use std::hint::unreachable_unchecked;
+use std::fmt::{Debug, Display};
+use std::hash::{BuildHasher, Hash};
 use std::mem::ManuallyDrop;
 use std::ptr;
 
+pub(crate) mod cursor;
 pub(crate) mod drain;
 pub(crate) mod entry;
 #[cfg(feature = "extract_if")]
 pub(crate) mod extract_if;
+pub(crate) mod inline_vec;
 pub(crate) mod iter;
+pub(crate) mod lookup;
+pub(crate) mod spilled;
 
-pub(crate) enum MapImpl<K, V, const N: usize> {
-    Heapless(heapless::Vec<(K, V), N>),
-    Spilled(HashMap<K, V>),
+/// The spilled map is boxed so that it doesn't dictate the size of `MapImpl`,
+/// which otherwise is at least as large as a `HashMap` no matter how small `N` is.
+/// The tag lives in the niche of the inline length, so a `MapImpl` is exactly
+/// as big as its inline storage whenever that is at least pointer-sized.
+///
+/// The heapless variant holds on to the allocator until the map spills, and the spilled map
+/// (and its box) are allocated through it.
+pub(crate) enum MapImpl<K, V, const N: usize, A: Allocator = Global> {
+    Heapless(InlineVec<(K, V), N>, A),
+    Spilled(SpilledBox<K, V, A>),
 }
 
 impl<K, V, const N: usize> MapImpl<K, V, N> {
     #[inline(always)]
     pub const fn new() -> Self {
-        Self::Heapless(heapless::Vec::new())
+        Self::new_in(Global)
+    }
+}
+
+impl<K, V, const N: usize, A: Allocator> MapImpl<K, V, N, A> {
+    #[inline(always)]
+    pub const fn new_in(alloc: A) -> Self {
+        Self::Heapless(InlineVec::new(), alloc)
+    }
+
+    #[inline]
+    pub fn allocator(&self) -> &A {
+        match self {
+            Self::Heapless(_, alloc) => alloc,
+            Self::Spilled(map) => spilled::allocator(map),
+        }
     }
 
     #[inline(always)]
@@ -36,7 +68,7 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     #[inline(always)]
     pub fn capacity(&self) -> usize {
         match self {
-            Self::Heapless(_) => N,
+            Self::Heapless(..) => N,
             Self::Spilled(m) => m.capacity(),
         }
     }
@@ -44,7 +76,7 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     #[inline]
     pub fn iter(&self) -> IterInner<'_, K, V, N> {
         match self {
-            Self::Heapless(vec) => IterInner::Heapless { next: 0, vec },
+            Self::Heapless(vec, _) => IterInner::Heapless { next: 0, vec },
             Self::Spilled(map) => IterInner::Spilled(map.iter()),
         }
     }
@@ -52,7 +84,7 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     #[inline]
     pub fn iter_mut(&mut self) -> IterMutInner<'_, K, V, N> {
         match self {
-            Self::Heapless(vec) => IterMutInner::Heapless(vec.iter_mut()),
+            Self::Heapless(vec, _) => IterMutInner::Heapless(vec.iter_mut()),
             Self::Spilled(map) => IterMutInner::Spilled(map.iter_mut()),
         }
     }
@@ -60,7 +92,7 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     #[inline]
     pub fn len(&self) -> usize {
         match self {
-            Self::Heapless(vec) => vec.len(),
+            Self::Heapless(vec, _) => vec.len(),
             Self::Spilled(map) => map.len(),
         }
     }
@@ -68,27 +100,27 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     #[inline]
     pub fn is_empty(&self) -> bool {
         match self {
-            Self::Heapless(vec) => vec.is_empty(),
+            Self::Heapless(vec, _) => vec.is_empty(),
             Self::Spilled(m) => m.is_empty(),
         }
     }
 
     #[inline]
-    pub fn drain(&mut self) -> DrainInner<'_, K, V, N> {
+    pub fn drain(&mut self) -> DrainInner<'_, K, V, N, A> {
         match self {
-            Self::Heapless(base) => DrainInner::Heapless(HeaplessDrain { base }),
+            Self::Heapless(base, _) => DrainInner::Heapless(HeaplessDrain { base }),
             Self::Spilled(map) => DrainInner::Spilled(map.drain()),
         }
     }
 
     #[cfg(feature = "extract_if")]
     #[inline]
-    pub fn extract_if<F>(&mut self, pred: F) -> extract_if::ExtractIfInner<'_, K, V, F, N>
+    pub fn extract_if<F>(&mut self, pred: F) -> extract_if::ExtractIfInner<'_, K, V, F, N, A>
     where
         F: FnMut(&K, &mut V) -> bool,
     {
         match self {
-            Self::Heapless(base) => extract_if::ExtractIfInner::Heapless {
+            Self::Heapless(base, _) => extract_if::ExtractIfInner::Heapless {
                 base,
                 next: 0,
                 pred,
@@ -103,7 +135,7 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
         F: FnMut(&K, &mut V) -> bool,
     {
         match self {
-            Self::Heapless(vec) => {
+            Self::Heapless(vec, _) => {
                 vec.retain_mut(|(k, v)| f(k, v));
             }
             Self::Spilled(map) => {
@@ -115,29 +147,53 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     #[inline]
     pub fn clear(&mut self) {
         match self {
-            Self::Heapless(vec) => vec.clear(),
+            Self::Heapless(vec, _) => vec.clear(),
             Self::Spilled(m) => m.clear(),
         }
     }
 
-    /// # Safety
-    ///
-    /// `MapImpl` must be in the `Heapless` variant.
     #[inline]
-    unsafe fn into_heapless_unchecked(self) -> heapless::Vec<(K, V), N> {
+    pub fn as_inline_slice(&self) -> Option<&[(K, V)]> {
         match self {
-            Self::Heapless(v) => v,
-            _ => unsafe { unreachable_unchecked() },
+            Self::Heapless(vec, _) => Some(vec),
+            Self::Spilled(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_hashmap(&self) -> Option<&HashMap<K, V, A>> {
+        match self {
+            Self::Heapless(..) => None,
+            Self::Spilled(map) => Some(map),
+        }
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> Storage<K, V, N, A> {
+        match self {
+            Self::Heapless(vec, _) => Storage::Inline(vec.into_heapless()),
+            Self::Spilled(map) => Storage::Spilled(spilled::unbox(map)),
         }
     }
 
+    // /// # Safety
+    // ///
+    // /// `MapImpl` must be in the `Heapless` variant.
+    // #[inline]
+    // unsafe fn into_heapless_unchecked(self) -> InlineVec<(K, V), N> {
+    //     match self {
+    //         Self::Heapless(v) => v,
+    //         _ => unsafe { unreachable_unchecked() },
+    //     }
+    // }
+
     /// # Safety
     ///
     /// `MapImpl` must be in the `Spilled` variant.
     #[inline]
-    unsafe fn into_spilled_unchecked(self) -> HashMap<K, V> {
+    unsafe fn into_spilled_unchecked(self) -> HashMap<K, V, A> {
         match self {
-            Self::Spilled(m) => m,
+            Self::Spilled(m) => spilled::unbox(m),
             _ => unsafe { unreachable_unchecked() },
         }
     }
@@ -146,9 +202,9 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     ///
     /// `MapImpl` must be in the `Heapless` variant.
     #[inline]
-    unsafe fn as_heapless_unchecked(&self) -> &heapless::Vec<(K, V), N> {
+    unsafe fn as_heapless_unchecked(&self) -> &InlineVec<(K, V), N> {
         match self {
-            Self::Heapless(m) => m,
+            Self::Heapless(m, _) => m,
             _ => unsafe { unreachable_unchecked() },
         }
     }
@@ -157,9 +213,9 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     ///
     /// `MapImpl` must be in the `Heapless` variant.
     #[inline]
-    unsafe fn as_heapless_mut_unchecked(&mut self) -> &mut heapless::Vec<(K, V), N> {
+    unsafe fn as_heapless_mut_unchecked(&mut self) -> &mut InlineVec<(K, V), N> {
         match self {
-            Self::Heapless(m) => m,
+            Self::Heapless(m, _) => m,
             _ => unsafe { unreachable_unchecked() },
         }
     }
@@ -168,7 +224,7 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     // ///
     // /// `MapImpl` must be in the `Spilled` variant.
     // #[inline]
-    // unsafe fn as_spilled_unchecked(&self) -> &HashMap<K, V> {
+    // unsafe fn as_spilled_unchecked(&self) -> &HashMap<K, V, A> {
     //     match self {
     //         Self::Spilled(m) => m,
     //         _ => unsafe { unreachable_unchecked() },
@@ -179,7 +235,7 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     ///
     /// `MapImpl` must be in the `Spilled` variant.
     #[inline]
-    unsafe fn as_spilled_mut_unchecked(&mut self) -> &mut HashMap<K, V> {
+    unsafe fn as_spilled_mut_unchecked(&mut self) -> &mut HashMap<K, V, A> {
         match self {
             Self::Spilled(m) => m,
             _ => unsafe { unreachable_unchecked() },
@@ -187,27 +243,36 @@ impl<K, V, const N: usize> MapImpl<K, V, N> {
     }
 }
 
-impl<K, V, const N: usize> MapImpl<K, V, N>
+impl<K, V, const N: usize, A: Allocator + Clone> MapImpl<K, V, N, A>
 where
     K: Eq + Hash,
 {
     #[inline]
     pub fn reserve(&mut self, additional: usize) {
-        if !self.spilled() && self.len() + additional > N {
-            // Safety: we just checked the variant
-            unsafe { self.try_spill(additional) }.unwrap();
-        } else {
-            // Safety: we just checked the variant
-            unsafe {
-                self.as_spilled_mut_unchecked().reserve(additional);
+        match self {
+            Self::Heapless(vec, _) => {
+                let len_needed = vec
+                    .len()
+                    .checked_add(additional)
+                    .expect("capacity overflow");
+                if len_needed > N {
+                    // Safety: we just checked the variant
+                    unsafe { self.try_spill(additional) }.unwrap();
+                }
+                // otherwise, we're good
             }
+            Self::Spilled(map) => map.reserve(additional),
         }
     }
 
     #[inline]
     pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
         if !self.spilled() {
-            if self.len() + additional > N {
+            let len_needed = self
+                .len()
+                .checked_add(additional)
+                .ok_or(TryReserveErrorKind::CapacityOverflow)?;
+            if len_needed > N {
                 // Safety: we just checked the variant
                 unsafe { self.try_spill(additional) }?;
             }
@@ -231,74 +296,103 @@ where
 
     pub fn shrink_into_heapless<const M: usize>(
         self,
-    ) -> Result<MapImpl<K, V, M>, MapImpl<K, V, N>> {
+    ) -> Result<MapImpl<K, V, M, A>, MapImpl<K, V, N, A>> {
         if self.len() > M {
             return Err(self);
         }
 
-        let heapless = match self {
-            MapImpl::Heapless(vec) => {
-                let vec = ManuallyDrop::new(vec);
-                let mut new = heapless::Vec::new();
-                unsafe {
-                    // Safety: vec won't be dropped, vec cannot overlap with new
-                    ptr::copy_nonoverlapping(vec.as_ptr(), new.as_mut_ptr(), vec.len());
-                    new.set_len(vec.len());
-                }
-                new
+        Ok(match self {
+            // Safety: we just checked the length
+            MapImpl::Heapless(vec, alloc) => {
+                MapImpl::Heapless(unsafe { move_heapless(vec) }, alloc)
             }
-            MapImpl::Spilled(map) => map.into_iter().collect::<heapless::Vec<(K, V), M>>(),
-        };
+            MapImpl::Spilled(map) => {
+                let alloc = spilled::allocator(&map).clone();
+                let vec = spilled::unbox(map).into_iter().collect();
+                MapImpl::Heapless(vec, alloc)
+            }
+        })
+    }
 
-        Ok(MapImpl::Heapless(heapless))
+    pub fn into_inline_capacity<const M: usize>(self) -> MapImpl<K, V, M, A> {
+        match self {
+            // Safety: we just checked the length
+            MapImpl::Heapless(vec, alloc) if vec.len() <= M => {
+                MapImpl::Heapless(unsafe { move_heapless(vec) }, alloc)
+            }
+            MapImpl::Heapless(vec, alloc) => {
+                let mut map = spilled::with_capacity_in(vec.len(), alloc);
+                map.extend(vec);
+                MapImpl::Spilled(spilled::boxed(map))
+            }
+            MapImpl::Spilled(map) => MapImpl::Spilled(map),
+        }
     }
 
     #[inline]
     pub fn shrink_to_fit(&mut self) {
-        if let Self::Spilled(map) = self {
-            map.shrink_to_fit()
-        }
+        self.shrink_to(0)
     }
 
     #[inline]
     pub fn shrink_to(&mut self, min_capacity: usize) {
         if let Self::Spilled(map) = self {
-            map.shrink_to(min_capacity)
+            if map.len().max(min_capacity) <= N {
+                // Safety: we just checked the variant and the length
+                unsafe { self.unspill() }
+            } else {
+                map.shrink_to(min_capacity)
+            }
+        }
+    }
+
+    /// Moves the entries of a spilled map back into inline storage.
+    ///
+    /// # Safety
+    ///
+    /// Must be in the `Spilled` variant, with no more than `N` entries.
+    unsafe fn unspill(&mut self) {
+        let alloc = self.allocator().clone();
+        let map = unsafe { std::mem::replace(self, Self::new_in(alloc)).into_spilled_unchecked() };
+        checked_assert!(map.len() <= N);
+        // Safety: we just swapped the variant
+        let vec = unsafe { self.as_heapless_mut_unchecked() };
+        for entry in map {
+            // Safety: the map has no more than `N` entries
+            unsafe { vec.push_unchecked(entry) };
         }
     }
 
     #[inline]
-    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, N> {
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, N, A> {
         match self {
-            Self::Heapless(vec) => {
-                if vec.is_empty() {
-                    Entry::Vacant(VacantEntry::Heapless(HeaplessEntry {
-                        key: Some(key),
-                        inner: self,
-                        index: 0,
-                    }))
-                } else if let Some(index) = vec.iter().position(|(k, _)| k == &key) {
-                    Entry::Occupied(OccupiedEntry::Heapless(HeaplessEntry {
-                        key: Some(key),
-                        inner: self,
-                        index,
-                    }))
+            Self::Heapless(vec, _) => {
+                let (index, occupied) = match vec.iter().position(|(k, _)| k == &key) {
+                    Some(index) => (index, true),
+                    None => (vec.len(), false),
+                };
+                let entry = HeaplessEntry {
+                    key: Some(key),
+                    inner: self,
+                    index,
+                };
+                if occupied {
+                    Entry::Occupied(OccupiedEntry {
+                        inner: OccupiedEntryInner::Heapless(entry),
+                    })
                 } else {
-                    let len = vec.len();
-                    Entry::Vacant(VacantEntry::Heapless(HeaplessEntry {
-                        key: Some(key),
-                        inner: self,
-                        index: len,
-                    }))
+                    Entry::Vacant(VacantEntry {
+                        inner: VacantEntryInner::Heapless(entry),
+                    })
                 }
             }
             Self::Spilled(map) => match map.entry(key) {
-                std::collections::hash_map::Entry::Occupied(entry) => {
-                    Entry::Occupied(OccupiedEntry::Spilled(entry))
-                }
-                std::collections::hash_map::Entry::Vacant(entry) => {
-                    Entry::Vacant(VacantEntry::Spilled(entry))
-                }
+                spilled::hash_map::Entry::Occupied(entry) => Entry::Occupied(OccupiedEntry {
+                    inner: OccupiedEntryInner::Spilled(entry),
+                }),
+                spilled::hash_map::Entry::Vacant(entry) => Entry::Vacant(VacantEntry {
+                    inner: VacantEntryInner::Spilled(entry),
+                }),
             },
         }
     }
@@ -306,8 +400,7 @@ where
     #[inline]
     pub fn get<Q>(&self, k: &Q) -> Option<&V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         match self.get_key_value(k) {
             Some((_, value)) => Some(value),
@@ -318,21 +411,20 @@ where
     #[inline]
     pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         match self {
-            Self::Heapless(vec) => {
+            Self::Heapless(vec, _) => {
                 if vec.is_empty() {
                     None
                 } else {
-                    match vec.iter().find(|(key, _)| key.borrow() == k) {
+                    match vec.iter().find(|(key, _)| k.matches(key)) {
                         Some((key, value)) => Some((key, value)),
                         None => None,
                     }
                 }
             }
-            Self::Spilled(map) => map.get_key_value(k),
+            Self::Spilled(map) => k.get_spilled(map),
         }
     }
 
@@ -340,28 +432,28 @@ where
     #[inline]
     pub fn get_many_mut<Q, const M: usize>(&mut self, ks: [&Q; M]) -> Option<[&'_ mut V; M]>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         match self {
-            Self::Heapless(vec) => {
-                let is =
-                    ks.map(|k| {
-                        vec.iter().enumerate().find_map(|(i, (key, _))| {
-                            if key.borrow() == k {
+            Self::Heapless(vec, _) => {
+                let is = ks.map(|k| {
+                    vec.iter().enumerate().find_map(
+                        |(i, (key, _))| {
+                            if k.matches(key) {
                                 Some(i)
                             } else {
                                 None
                             }
-                        })
-                    });
+                        },
+                    )
+                });
                 if is.iter().any(|i| i.is_none()) {
                     return None;
                 }
-                let is = is.map(|i| unsafe { i.unwrap_unchecked() });
+                let is = is.map(|i| unsafe { crate::utils::unwrap_unchecked(i) });
                 Some(vec.get_many_mut(is).ok()?.map(|(_, v)| v))
             }
-            Self::Spilled(map) => map.get_many_mut(ks),
+            Self::Spilled(map) => Q::get_many_spilled_mut(ks, map),
         }
     }
 
@@ -376,7 +468,7 @@ where
         Q: Hash + Eq + ?Sized,
     {
         match self {
-            Self::Heapless(vec) => {
+            Self::Heapless(vec, _) => {
                 let is =
                     ks.map(|k| {
                         vec.iter().enumerate().find_map(|(i, (key, _))| {
@@ -390,7 +482,7 @@ where
                 if is.iter().any(|i| i.is_none()) {
                     return None;
                 }
-                let is = is.map(|i| unsafe { i.unwrap_unchecked() });
+                let is = is.map(|i| unsafe { crate::utils::unwrap_unchecked(i) });
                 let es = unsafe { vec.get_many_unchecked_mut(is) };
                 Some(es.map(|(_, v)| v))
             }
@@ -401,8 +493,7 @@ where
     #[inline]
     pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.get(k).is_some()
     }
@@ -410,27 +501,26 @@ where
     #[inline]
     pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         match self {
-            Self::Heapless(vec) => {
+            Self::Heapless(vec, _) => {
                 if vec.is_empty() {
                     None
                 } else {
-                    match vec.iter_mut().find(|(key, _)| key.borrow() == k) {
+                    match vec.iter_mut().find(|(key, _)| k.matches(key)) {
                         Some((_, value)) => Some(value),
                         None => None,
                     }
                 }
             }
-            Self::Spilled(map) => map.get_mut(k),
+            Self::Spilled(map) => k.get_spilled_mut(map),
         }
     }
 
     pub fn insert(&mut self, k: K, v: V) -> Option<V> {
         match self {
-            Self::Heapless(vec) => {
+            Self::Heapless(vec, _) => {
                 // Scan for equivalent key
                 for (key, value) in vec.iter_mut() {
                     if key == &k {
@@ -454,11 +544,196 @@ where
         }
     }
 
+    pub fn try_map_values<W, E, F>(self, mut f: F) -> Result<MapImpl<K, W, N, A>, E>
+    where
+        F: FnMut(&K, V) -> Result<W, E>,
+    {
+        match self {
+            Self::Heapless(vec, alloc) => {
+                let mut mapped = InlineVec::new();
+                for (k, v) in vec {
+                    let w = f(&k, v)?;
+                    checked_assert!(!mapped.is_full());
+                    // Safety: `mapped` holds no more entries than `vec` did
+                    unsafe { mapped.push_unchecked((k, w)) };
+                }
+                Ok(MapImpl::Heapless(mapped, alloc))
+            }
+            Self::Spilled(map) => {
+                let mut mapped = spilled::with_capacity_and_hasher_in(
+                    map.capacity(),
+                    map.hasher().clone(),
+                    spilled::allocator(&map).clone(),
+                );
+                for (k, v) in spilled::unbox(map) {
+                    let w = f(&k, v)?;
+                    // the keys were unique in `map`
+                    mapped.insert_unique_unchecked(k, w);
+                }
+                Ok(MapImpl::Spilled(spilled::boxed(mapped)))
+            }
+        }
+    }
+
+    #[inline]
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        match self {
+            Self::Heapless(vec, _) => vec.get(index).map(|(k, v)| (k, v)),
+            Self::Spilled(map) => map.iter().nth(index),
+        }
+    }
+
+    #[inline]
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        match self {
+            Self::Heapless(vec, _) => vec.get_mut(index).map(|(k, v)| (&*k, v)),
+            Self::Spilled(map) => map.iter_mut().nth(index),
+        }
+    }
+
+    #[inline]
+    pub fn pop_any(&mut self) -> Option<(K, V)> {
+        match self {
+            Self::Heapless(vec, _) => vec.pop(),
+            Self::Spilled(map) => spilled::pop_any(map),
+        }
+    }
+
+    #[inline]
+    pub fn swap_remove_index(&mut self, index: usize) -> Option<(K, V)> {
+        match self {
+            Self::Heapless(vec, _) => {
+                if index < vec.len() {
+                    // Safety: index is in bounds
+                    Some(unsafe { vec.swap_remove_unchecked(index) })
+                } else {
+                    None
+                }
+            }
+            Self::Spilled(map) => spilled::remove_nth(map, index),
+        }
+    }
+
+    pub fn partition<F>(self, mut pred: F) -> (Self, Self)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let alloc = self.allocator().clone();
+        let (mut left, mut right) = (Self::new_in(alloc.clone()), Self::new_in(alloc));
+        for (k, v) in self {
+            let side = if pred(&k, &v) { &mut left } else { &mut right };
+            // Safety: keys of a map are unique
+            unsafe { side.insert_unique_unchecked(k, v) };
+        }
+        (left, right)
+    }
+
+    pub fn split_off_if<F, const M: usize>(&mut self, mut pred: F) -> MapImpl<K, V, M, A>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let alloc = self.allocator().clone();
+        let mut guard = SplitGuard {
+            map: self,
+            other: MapImpl::new_in(alloc.clone()),
+        };
+        match guard.map {
+            Self::Heapless(vec, _) => {
+                let mut index = 0;
+                while index < vec.len() {
+                    // Safety: index is in bounds
+                    let (k, v) = unsafe { vec.get_unchecked(index) };
+                    if pred(k, v) {
+                        // Safety: index is in bounds
+                        let (k, v) = unsafe { vec.swap_remove_unchecked(index) };
+                        // Safety: keys of a map are unique
+                        unsafe { guard.other.insert_unique_unchecked(k, v) };
+                    } else {
+                        index += 1;
+                    }
+                }
+            }
+            Self::Spilled(map) => {
+                for (k, v) in map.extract_if(|k, v| pred(k, v)) {
+                    // Safety: keys of a map are unique
+                    unsafe { guard.other.insert_unique_unchecked(k, v) };
+                }
+            }
+        }
+        std::mem::replace(&mut guard.other, MapImpl::new_in(alloc))
+    }
+
+    pub fn alter<F>(&mut self, key: K, f: F)
+    where
+        F: FnOnce(Option<V>) -> Option<V>,
+    {
+        match self {
+            Self::Heapless(vec, _) => match vec.iter().position(|(k, _)| k == &key) {
+                Some(index) => {
+                    checked_assert!(index < vec.len());
+                    // Safety: index is in bounds
+                    let (k, v) = unsafe { vec.swap_remove_unchecked(index) };
+                    if let Some(v) = f(Some(v)) {
+                        // Safety: we just removed an element
+                        unsafe { vec.push_unchecked((k, v)) };
+                    }
+                }
+                None => {
+                    if let Some(v) = f(None) {
+                        if let Err((k, v)) = vec.push((key, v)) {
+                            // Safety: we are still in the heapless variant
+                            let map = unsafe { self.try_spill(1) };
+                            map.unwrap().insert(k, v);
+                        }
+                    }
+                }
+            },
+            Self::Spilled(map) => match map.remove_entry(&key) {
+                Some((k, v)) => {
+                    if let Some(v) = f(Some(v)) {
+                        map.insert(k, v);
+                    }
+                }
+                None => {
+                    if let Some(v) = f(None) {
+                        map.insert(key, v);
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn update<Q, F>(&mut self, k: &Q, f: F)
+    where
+        Q: Lookup<K> + ?Sized,
+        F: FnOnce(&mut V) -> bool,
+    {
+        match self {
+            Self::Heapless(vec, _) => {
+                if let Some(index) = vec.iter().position(|(key, _)| k.matches(key)) {
+                    checked_assert!(index < vec.len());
+                    // Safety: index is in bounds
+                    let keep = f(unsafe { &mut vec.get_unchecked_mut(index).1 });
+                    if !keep {
+                        // Safety: index is in bounds
+                        unsafe { vec.swap_remove_unchecked(index) };
+                    }
+                }
+            }
+            Self::Spilled(map) => {
+                if let Some(v) = k.get_spilled_mut(map) {
+                    if !f(v) {
+                        k.remove_spilled(map);
+                    }
+                }
+            }
+        }
+    }
+
     #[inline]
     pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         match self.remove_entry(k) {
             Some((_, value)) => Some(value),
@@ -469,22 +744,22 @@ where
     #[inline]
     pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         match self {
-            Self::Heapless(vec) => {
+            Self::Heapless(vec, _) => {
                 // find index
-                let index = vec.iter().position(|(key, _)| key.borrow() == k)?;
+                let index = vec.iter().position(|(key, _)| k.matches(key))?;
+                checked_assert!(index < vec.len());
                 // Safety: index is in bounds
                 Some(unsafe { vec.swap_remove_unchecked(index) })
             }
-            Self::Spilled(m) => m.remove_entry(k),
+            Self::Spilled(m) => k.remove_spilled(m),
         }
     }
 
     #[inline]
-    pub fn into_hashmap(mut self) -> HashMap<K, V> {
+    pub fn into_hashmap(mut self) -> HashMap<K, V, A> {
         if !self.spilled() {
             // Safety: we just checked the variant
             unsafe { self.try_spill(0) }.unwrap();
@@ -493,6 +768,14 @@ where
         unsafe { self.into_spilled_unchecked() }
     }
 
+    /// Moves the inline entries into a `HashMap` with room for `N + additional` entries.
+    ///
+    /// If `K::hash` panics, the map stays in the `Heapless` variant with all its entries:
+    /// each key is hashed while its entry is still in the vec, and the entry is then
+    /// inserted with that hash into the table reserved up front, which can't fail. The
+    /// entries moved so far are put back on unwind. Keys are never compared, as they are
+    /// unique already.
+    ///
     /// # Safety
     ///
     /// Must be in the `Heapless` variant.
@@ -500,44 +783,339 @@ where
     unsafe fn try_spill(
         &mut self,
         additional: usize,
-    ) -> Result<&mut HashMap<K, V>, TryReserveError> {
+    ) -> Result<&mut HashMap<K, V, A>, TryReserveError> {
+        checked_assert!(!self.spilled());
         let cap_needed = N
             .checked_add(additional)
-            .ok_or(TryReserveError { kind: () })?;
-        let mut map = HashMap::new();
+            .ok_or(TryReserveErrorKind::CapacityOverflow)?;
+        let mut map = spilled::with_capacity_in(0, self.allocator().clone());
         map.try_reserve(cap_needed)?;
-        let vec = std::mem::replace(self, Self::Spilled(map));
-        let (vec, map) = unsafe {
-            // Safety: we just swapped the variant
-            (
-                vec.into_heapless_unchecked(),
-                self.as_spilled_mut_unchecked(),
-            )
-        };
-        map.extend(vec);
-        Ok(map)
+
+        // Safety: guaranteed by the caller
+        let vec = unsafe { self.as_heapless_mut_unchecked() };
+        let guard = SpillGuard::<K, V, N, A> { vec, map: &mut map };
+        while let Some((k, _)) = guard.vec.last() {
+            let hash = guard.map.hasher().hash_one(k);
+            // Safety: we just checked that the vec is not empty
+            let entry = unsafe { crate::utils::unwrap_unchecked(guard.vec.pop()) };
+            checked_assert!(guard.map.len() < guard.map.capacity());
+            // Safety: room for every inline entry was reserved above
+            unsafe { guard.map.raw_table_mut().insert_no_grow(hash, entry) };
+        }
+        std::mem::forget(guard);
+
+        *self = Self::Spilled(spilled::boxed(map));
+        // Safety: we just swapped the variant
+        Ok(unsafe { self.as_spilled_mut_unchecked() })
+    }
+
+    pub fn assert_invariants(&self) {
+        match self {
+            Self::Heapless(vec, _) => {
+                assert!(vec.len() <= N, "inline length exceeds capacity");
+                for (i, (key, _)) in vec.iter().enumerate() {
+                    assert!(
+                        vec[i + 1..].iter().all(|(other, _)| other != key),
+                        "duplicate key in inline storage"
+                    );
+                }
+            }
+            Self::Spilled(map) => {
+                assert!(map.len() <= map.capacity(), "length exceeds capacity");
+            }
+        }
     }
 
     pub fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let mut iter = iter.into_iter();
+        if !self.spilled() && !self.spill_for_size_hint(&iter) {
+            for (k, v) in iter.by_ref() {
+                self.insert(k, v);
+                if self.spilled() {
+                    break;
+                }
+            }
+        }
+        if let MapImpl::Spilled(map) = self {
+            map.extend(iter);
+        }
+    }
+
+    /// Inserts all pairs of `iter`, stopping at the first key already in the map.
+    pub fn extend_strict<T>(&mut self, iter: T) -> Result<(), (K, V)>
+    where
+        T: IntoIterator<Item = (K, V)>,
+    {
+        let mut iter = iter.into_iter();
+        if !self.spilled() {
+            self.spill_for_size_hint(&iter);
+        }
+        while let Some((k, v)) = iter.next() {
+            match self {
+                Self::Heapless(vec, _) => {
+                    if vec.iter().any(|(key, _)| key == &k) {
+                        return Err((k, v));
+                    }
+                    if let Err((k, v)) = vec.push((k, v)) {
+                        // Safety: we are still in the heapless variant
+                        let map = unsafe { self.try_spill(1 + iter.size_hint().0) };
+                        map.unwrap().insert(k, v);
+                    }
+                }
+                Self::Spilled(map) => {
+                    if map.contains_key(&k) {
+                        return Err((k, v));
+                    }
+                    map.insert(k, v);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn merge_with<T, F>(&mut self, iter: T, mut f: F)
+    where
+        T: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, V, V) -> V,
+    {
+        let mut iter = iter.into_iter();
+        if !self.spilled() {
+            self.spill_for_size_hint(&iter);
+        }
+        while let Some((k, v)) = iter.next() {
+            match self {
+                Self::Heapless(vec, _) => match vec.iter().position(|(key, _)| key == &k) {
+                    Some(index) => {
+                        checked_assert!(index < vec.len());
+                        // Safety: index is in bounds
+                        let (key, old) = unsafe { vec.swap_remove_unchecked(index) };
+                        let new = f(&key, old, v);
+                        // Safety: we just removed an element
+                        unsafe { vec.push_unchecked((key, new)) };
+                    }
+                    None => {
+                        if let Err((k, v)) = vec.push((k, v)) {
+                            // Safety: we are still in the heapless variant
+                            let map = unsafe { self.try_spill(1 + iter.size_hint().0) };
+                            map.unwrap().insert(k, v);
+                        }
+                    }
+                },
+                Self::Spilled(map) => match map.remove_entry(&k) {
+                    Some((key, old)) => {
+                        let new = f(&key, old, v);
+                        map.insert(key, new);
+                    }
+                    None => {
+                        map.insert(k, v);
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn append<const M: usize>(&mut self, other: &mut MapImpl<K, V, M, A>) {
+        match (&mut *self, other) {
+            (_, other) if other.is_empty() => {}
+            (MapImpl::Heapless(ours, _), MapImpl::Heapless(theirs, _)) => {
+                // Values from `other` win, like `BTreeMap::append`.
+                let mut i = 0;
+                while i < theirs.len() {
+                    match ours.iter_mut().find(|(k, _)| k == &theirs[i].0) {
+                        Some((_, v)) => {
+                            checked_assert!(i < theirs.len());
+                            // Safety: i is in bounds
+                            *v = unsafe { theirs.swap_remove_unchecked(i) }.1;
+                        }
+                        None => i += 1,
+                    }
+                }
+                // The remaining keys are all new, move them in one go if they fit.
+                let (len, additional) = (ours.len(), theirs.len());
+                if len + additional <= N {
+                    unsafe {
+                        // Safety: both are distinct vecs, and the total fits in `N`
+                        ptr::copy_nonoverlapping(
+                            theirs.as_ptr(),
+                            ours.as_mut_ptr().add(len),
+                            additional,
+                        );
+                        // Safety: the entries were moved out, so they won't be dropped twice
+                        theirs.set_len(0);
+                        ours.set_len(len + additional);
+                    }
+                } else {
+                    // Safety: we just checked the variant
+                    let map = unsafe { self.try_spill(additional) }.unwrap();
+                    map.extend(std::iter::from_fn(|| theirs.pop()));
+                }
+            }
+            (ours, other @ MapImpl::Spilled(_)) if other.len() >= ours.len() => {
+                // Steal the larger allocation, and move our own entries into it.
+                let alloc = other.allocator().clone();
+                let theirs = std::mem::replace(other, MapImpl::new_in(alloc));
+                // Safety: we just checked the variant
+                let theirs = unsafe { theirs.into_spilled_unchecked() };
+                let ours = std::mem::replace(ours, MapImpl::Spilled(spilled::boxed(theirs)));
+                // Safety: we just swapped the variant
+                let map = unsafe { self.as_spilled_mut_unchecked() };
+                map.reserve(ours.len());
+                for (k, v) in ours {
+                    map.entry(k).or_insert(v);
+                }
+            }
+            (_, other) => {
+                let additional = other.len();
+                let map = match self {
+                    // Safety: we just checked the variant
+                    MapImpl::Heapless(..) => unsafe { self.try_spill(additional) }.unwrap(),
+                    MapImpl::Spilled(map) => {
+                        map.reserve(additional);
+                        map
+                    }
+                };
+                map.extend(other.drain());
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// No key yielded by `iter` may be in the map already or yielded twice.
+    pub unsafe fn extend_unique<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let mut iter = iter.into_iter();
+        if !self.spilled() && !self.spill_for_size_hint(&iter) {
+            // Safety: we just checked the variant
+            let vec = unsafe { self.as_heapless_mut_unchecked() };
+            for (k, v) in iter.by_ref() {
+                if let Err((k, v)) = vec.push((k, v)) {
+                    // Safety: we are still in the heapless variant
+                    let map = unsafe { self.try_spill(1 + iter.size_hint().0) };
+                    map.unwrap().insert(k, v);
+                    break;
+                }
+            }
+        }
         if let MapImpl::Spilled(map) = self {
             map.extend(iter);
-            return;
         }
-        for (k, v) in iter {
-            self.insert(k, v);
+    }
+
+    /// # Safety
+    ///
+    /// `k` must not be in the map already.
+    pub unsafe fn insert_unique_unchecked(&mut self, k: K, v: V) -> &mut V {
+        if let Self::Heapless(vec, _) = self {
+            if vec.is_full() {
+                // Safety: we just checked the variant
+                unsafe { self.try_spill(1) }.unwrap();
+            }
+        }
+        match self {
+            Self::Heapless(vec, _) => {
+                checked_assert!(!vec.is_full());
+                // Safety: we just checked that the vec is not full
+                unsafe { vec.push_unchecked((k, v)) };
+                let index = vec.len() - 1;
+                // Safety: we just pushed the entry
+                unsafe { &mut vec.get_unchecked_mut(index).1 }
+            }
+            Self::Spilled(map) => map.insert_unique_unchecked(k, v).1,
+        }
+    }
+
+    /// Spills straight to a `HashMap` sized for `iter` if its lower bound doesn't fit inline.
+    /// Returns whether it spilled.
+    ///
+    /// Must be in the `Heapless` variant.
+    #[inline]
+    fn spill_for_size_hint<I: Iterator>(&mut self, iter: &I) -> bool {
+        checked_assert!(!self.spilled());
+        let lower = iter.size_hint().0;
+        if self.len().saturating_add(lower) > N {
+            // Safety: guaranteed by the caller
+            unsafe { self.try_spill(lower) }.unwrap();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Puts the entries moved into `map` back into `vec` if `try_spill` unwinds.
+struct SpillGuard<'a, K, V, const N: usize, A: Allocator> {
+    vec: &'a mut InlineVec<(K, V), N>,
+    map: &'a mut HashMap<K, V, A>,
+}
+
+impl<K, V, const N: usize, A: Allocator> Drop for SpillGuard<'_, K, V, N, A> {
+    fn drop(&mut self) {
+        for entry in self.map.drain() {
+            checked_assert!(!self.vec.is_full());
+            // Safety: every entry of the map was popped from the vec
+            unsafe { self.vec.push_unchecked(entry) };
+        }
+    }
+}
+
+/// Moves the entries split off into `other` back into `map` if `split_off_if` unwinds.
+struct SplitGuard<'a, K: Eq + Hash, V, const N: usize, const M: usize, A: Allocator + Clone> {
+    map: &'a mut MapImpl<K, V, N, A>,
+    other: MapImpl<K, V, M, A>,
+}
+
+impl<K, V, const N: usize, const M: usize, A> Drop for SplitGuard<'_, K, V, N, M, A>
+where
+    K: Eq + Hash,
+    A: Allocator + Clone,
+{
+    fn drop(&mut self) {
+        let alloc = self.other.allocator().clone();
+        for (k, v) in std::mem::replace(&mut self.other, MapImpl::new_in(alloc)) {
+            match self.map {
+                MapImpl::Heapless(vec, _) => {
+                    checked_assert!(!vec.is_full());
+                    // Safety: every entry of `other` was removed from the vec
+                    unsafe { vec.push_unchecked((k, v)) };
+                }
+                // `extract_if` doesn't shrink the table, so this only hashes the key again
+                MapImpl::Spilled(map) => {
+                    map.insert_unique_unchecked(k, v);
+                }
+            }
         }
     }
 }
 
-impl<K, V, const N: usize> IntoIterator for MapImpl<K, V, N> {
+/// Moves the entries of `vec` into an `InlineVec` of another capacity.
+///
+/// # Safety
+///
+/// `vec` must have no more than `M` entries.
+#[inline]
+unsafe fn move_heapless<K, V, const N: usize, const M: usize>(
+    vec: InlineVec<(K, V), N>,
+) -> InlineVec<(K, V), M> {
+    checked_assert!(vec.len() <= M);
+    let vec = ManuallyDrop::new(vec);
+    let mut new = InlineVec::new();
+    unsafe {
+        // Safety: vec won't be dropped, vec cannot overlap with new
+        ptr::copy_nonoverlapping(vec.as_ptr(), new.as_mut_ptr(), vec.len());
+        new.set_len(vec.len());
+    }
+    new
+}
+
+impl<K, V, const N: usize, A: Allocator> IntoIterator for MapImpl<K, V, N, A> {
     type Item = (K, V);
-    type IntoIter = IntoIterInner<K, V, N>;
+    type IntoIter = IntoIterInner<K, V, N, A>;
 
     #[inline]
-    fn into_iter(self) -> IntoIterInner<K, V, N> {
+    fn into_iter(self) -> IntoIterInner<K, V, N, A> {
         match self {
-            MapImpl::Heapless(vec) => IntoIterInner::Heapless(vec),
-            MapImpl::Spilled(map) => IntoIterInner::Spilled(map.into_iter()),
+            MapImpl::Heapless(vec, _) => IntoIterInner::Heapless(vec),
+            MapImpl::Spilled(map) => IntoIterInner::Spilled(spilled::unbox(map).into_iter()),
         }
     }
 }
@@ -548,9 +1126,35 @@ where
 {
     fn from(arr: [(K, V); N]) -> Self {
         if N <= M {
-            Self::Heapless(heapless::Vec::from_iter(arr))
+            let mut map = Self::new();
+            for (k, v) in arr {
+                // never spills: there are at most `N <= M` distinct keys
+                map.insert(k, v);
+            }
+            map
         } else {
-            Self::Spilled(HashMap::from(arr))
+            let mut map = spilled::with_capacity_in(N, Global);
+            map.extend(arr);
+            Self::Spilled(spilled::boxed(map))
+        }
+    }
+}
+
+/// The underlying storage of a [`CompactMap`](crate::CompactMap).
+///
+/// This `enum` is returned by the [`into_inner`](crate::CompactMap::into_inner) method.
+pub enum Storage<K, V, const N: usize, A: Allocator = Global> {
+    /// The entries stored inline, in arbitrary order.
+    Inline(heapless::Vec<(K, V), N>),
+    /// The entries spilled into a `HashMap`.
+    Spilled(crate::SpilledMap<K, V, std::collections::hash_map::RandomState, A>),
+}
+
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for Storage<K, V, N, A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Inline(vec) => f.debug_tuple("Inline").field(vec).finish(),
+            Self::Spilled(map) => f.debug_tuple("Spilled").field(map).finish(),
         }
     }
 }
@@ -558,19 +1162,138 @@ where
 /// The error type for `try_reserve` methods.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct TryReserveError {
-    kind: (),
+    kind: TryReserveErrorKind,
+}
+
+/// Details of the allocation that caused a [`TryReserveError`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum TryReserveErrorKind {
+    /// Error due to the computed capacity exceeding the collection's maximum
+    /// (usually `isize::MAX` bytes), or to `N + additional` overflowing [`usize`].
+    CapacityOverflow,
+
+    /// The memory allocator returned an error.
+    AllocError {
+        /// The layout of the allocation request that failed.
+        ///
+        /// std only exposes it on nightly, so this is `None` for errors converted from
+        /// std's unless the `try_reserve_kind` feature is enabled.
+        layout: Option<Layout>,
+    },
+}
+
+impl TryReserveError {
+    /// Details about the allocation that caused the error.
+    #[inline]
+    pub fn kind(&self) -> TryReserveErrorKind {
+        self.kind
+    }
 }
 
+impl From<TryReserveErrorKind> for TryReserveError {
+    #[inline]
+    fn from(kind: TryReserveErrorKind) -> Self {
+        Self { kind }
+    }
+}
+
+#[cfg(feature = "try_reserve_kind")]
+impl From<std::collections::TryReserveError> for TryReserveError {
+    fn from(err: std::collections::TryReserveError) -> Self {
+        use std::collections::TryReserveErrorKind as StdKind;
+        let kind = match err.kind() {
+            StdKind::CapacityOverflow => TryReserveErrorKind::CapacityOverflow,
+            StdKind::AllocError { layout, .. } => TryReserveErrorKind::AllocError {
+                layout: Some(layout),
+            },
+        };
+        Self { kind }
+    }
+}
+
+#[cfg(not(feature = "try_reserve_kind"))]
 impl From<std::collections::TryReserveError> for TryReserveError {
-    fn from(_: std::collections::TryReserveError) -> Self {
-        Self { kind: () }
+    fn from(err: std::collections::TryReserveError) -> Self {
+        // std errors compare equal by kind, and asking a `Vec` for more than `isize::MAX` bytes
+        // is always a capacity overflow, so this tells the two kinds apart on stable.
+        let overflow = Vec::<u8>::new().try_reserve(usize::MAX).unwrap_err();
+        let kind = if err == overflow {
+            TryReserveErrorKind::CapacityOverflow
+        } else {
+            TryReserveErrorKind::AllocError { layout: None }
+        };
+        Self { kind }
+    }
+}
+
+impl From<hashbrown::TryReserveError> for TryReserveError {
+    fn from(err: hashbrown::TryReserveError) -> Self {
+        let kind = match err {
+            hashbrown::TryReserveError::CapacityOverflow => TryReserveErrorKind::CapacityOverflow,
+            hashbrown::TryReserveError::AllocError { layout } => TryReserveErrorKind::AllocError {
+                layout: Some(layout),
+            },
+        };
+        Self { kind }
     }
 }
 
 impl Display for TryReserveError {
     fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
-        fmt.write_str("memory allocation failed")
+        fmt.write_str("memory allocation failed")?;
+        let reason = match self.kind {
+            TryReserveErrorKind::CapacityOverflow => {
+                " because the computed capacity exceeded the collection's maximum"
+            }
+            TryReserveErrorKind::AllocError { .. } => {
+                " because the memory allocator returned an error"
+            }
+        };
+        fmt.write_str(reason)
     }
 }
 
 impl std::error::Error for TryReserveError {}
+
+/// The error returned by [`CompactMap::try_from_unique_iter`](crate::CompactMap::try_from_unique_iter)
+/// when the iterator yields the same key twice.
+///
+/// Holds the first key-value pair whose key was already yielded.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DuplicateKeyError<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<K, V> DuplicateKeyError<K, V> {
+    pub(crate) fn new(key: K, value: V) -> Self {
+        Self { key, value }
+    }
+
+    /// The duplicate key.
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// The value that was paired with the duplicate key.
+    #[inline]
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    /// Returns the colliding key-value pair.
+    #[inline]
+    pub fn into_inner(self) -> (K, V) {
+        (self.key, self.value)
+    }
+}
+
+impl<K: Debug, V> Display for DuplicateKeyError<K, V> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(fmt, "duplicate key {:?}", self.key)
+    }
+}
+
+impl<K: Debug, V: Debug> std::error::Error for DuplicateKeyError<K, V> {}
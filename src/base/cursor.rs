@@ -0,0 +1,222 @@
+use crate::base::spilled::{self, Allocator, Global};
+use crate::base::{inline_vec::InlineVec, MapImpl};
+use crate::utils::checked_assert;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::mem;
+
+/// A cursor over the entries of a [`CompactMap`], which can update or remove each entry
+/// while traversing the map.
+///
+/// This `struct` is created by the [`cursor_mut`] method on [`CompactMap`].
+///
+/// [`cursor_mut`]: crate::CompactMap::cursor_mut
+/// [`CompactMap`]: crate::CompactMap
+pub struct CursorMut<'a, K, V, const N: usize, A: Allocator = Global>
+where
+    K: Eq + Hash,
+{
+    inner: CursorInner<'a, K, V, N, A>,
+}
+
+enum CursorInner<'a, K: Eq + Hash, V, const N: usize, A: Allocator> {
+    Heapless {
+        vec: &'a mut InlineVec<(K, V), N>,
+        index: usize,
+        // `false` if the entry at `index` was swapped in by a removal and is yet to be visited
+        advance: bool,
+    },
+    Spilled(spilled::RawCursor<'a, K, V, A>),
+}
+
+/// A handle to the entry a [`CursorMut`] is at.
+///
+/// It behaves like an [`OccupiedEntry`](crate::OccupiedEntry).
+pub struct CursorEntry<'c, K, V, const N: usize, A: Allocator = Global> {
+    inner: CursorEntryInner<'c, K, V, N, A>,
+}
+
+enum CursorEntryInner<'c, K, V, const N: usize, A: Allocator> {
+    Heapless {
+        vec: &'c mut InlineVec<(K, V), N>,
+        index: usize,
+        advance: &'c mut bool,
+    },
+    Spilled(spilled::RawEntry<'c, K, V, A>),
+}
+
+impl<'a, K, V, const N: usize, A: Allocator + Clone> CursorMut<'a, K, V, N, A>
+where
+    K: Eq + Hash,
+{
+    pub(crate) fn new(base: &'a mut MapImpl<K, V, N, A>) -> Self {
+        let inner = match base {
+            MapImpl::Heapless(vec, _) => CursorInner::Heapless {
+                vec,
+                index: 0,
+                advance: false,
+            },
+            MapImpl::Spilled(map) => CursorInner::Spilled(spilled::RawCursor::new(map)),
+        };
+        Self { inner }
+    }
+
+    /// Moves the cursor to the next entry, and returns a handle to it.
+    ///
+    /// Returns `None` once every entry has been visited.
+    #[allow(clippy::should_implement_trait)] // the entry borrows the cursor
+    pub fn next(&mut self) -> Option<CursorEntry<'_, K, V, N, A>> {
+        let inner = match &mut self.inner {
+            CursorInner::Heapless {
+                vec,
+                index,
+                advance,
+            } => {
+                if mem::replace(advance, true) {
+                    *index += 1;
+                }
+                if *index >= vec.len() {
+                    *advance = false;
+                    return None;
+                }
+                CursorEntryInner::Heapless {
+                    vec,
+                    index: *index,
+                    advance,
+                }
+            }
+            CursorInner::Spilled(cursor) => CursorEntryInner::Spilled(cursor.next()?),
+        };
+        Some(CursorEntry { inner })
+    }
+}
+
+impl<K, V, const N: usize, A: Allocator> Debug for CursorMut<'_, K, V, N, A>
+where
+    K: Eq + Hash,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CursorMut").finish_non_exhaustive()
+    }
+}
+
+impl<'c, K, V, const N: usize, A: Allocator> CursorEntry<'c, K, V, N, A> {
+    #[inline]
+    fn entry(&self) -> &(K, V) {
+        match &self.inner {
+            CursorEntryInner::Heapless { vec, index, .. } => {
+                checked_assert!(*index < vec.len());
+                // SAFETY: the cursor only hands out entries in bounds
+                unsafe { vec.get_unchecked(*index) }
+            }
+            CursorEntryInner::Spilled(entry) => entry.get(),
+        }
+    }
+
+    #[inline]
+    fn entry_mut(&mut self) -> &mut (K, V) {
+        match &mut self.inner {
+            CursorEntryInner::Heapless { vec, index, .. } => {
+                checked_assert!(*index < vec.len());
+                // SAFETY: the cursor only hands out entries in bounds
+                unsafe { vec.get_unchecked_mut(*index) }
+            }
+            CursorEntryInner::Spilled(entry) => entry.get_mut(),
+        }
+    }
+
+    /// Gets a reference to the key in the entry.
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.entry().0
+    }
+
+    /// Gets a reference to the value in the entry.
+    #[inline]
+    pub fn get(&self) -> &V {
+        &self.entry().1
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.entry_mut().1
+    }
+
+    /// Converts the handle into a mutable reference to the value in the entry
+    /// with a lifetime bound to the cursor.
+    #[inline]
+    pub fn into_mut(self) -> &'c mut V {
+        match self.inner {
+            CursorEntryInner::Heapless { vec, index, .. } => {
+                checked_assert!(index < vec.len());
+                // SAFETY: the cursor only hands out entries in bounds
+                unsafe { &mut vec.get_unchecked_mut(index).1 }
+            }
+            CursorEntryInner::Spilled(entry) => &mut entry.into_mut().1,
+        }
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value out of the map, and returns it.
+    #[inline]
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the key and value out of the map, and returns them.
+    ///
+    /// The cursor still visits every other entry.
+    #[inline]
+    pub fn remove_entry(self) -> (K, V) {
+        match self.inner {
+            CursorEntryInner::Heapless {
+                vec,
+                index,
+                advance,
+            } => {
+                checked_assert!(index < vec.len());
+                // the last entry is swapped into `index`, so the cursor must visit it next
+                *advance = false;
+                // SAFETY: the cursor only hands out entries in bounds
+                unsafe { vec.swap_remove_unchecked(index) }
+            }
+            CursorEntryInner::Spilled(entry) => entry.remove(),
+        }
+    }
+}
+
+impl<K: Eq, V, const N: usize, A: Allocator> CursorEntry<'_, K, V, N, A> {
+    /// Replaces the key of the entry with `key`, and returns the old key.
+    ///
+    /// This is useful to swap in a key that is equal but cheaper to keep around,
+    /// e.g. a shared handle to an interned string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not equal to the key of the entry.
+    #[inline]
+    pub fn replace_key(&mut self, key: K) -> K {
+        let old = &mut self.entry_mut().0;
+        assert!(
+            *old == key,
+            "replace_key with a key not equal to the entry's"
+        );
+        mem::replace(old, key)
+    }
+}
+
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for CursorEntry<'_, K, V, N, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CursorEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
@@ -1,14 +1,15 @@
-use std::collections::hash_map;
+use crate::base::inline_vec::InlineVec;
+use crate::base::spilled::{self, Allocator, Global};
 use std::fmt;
 use std::fmt::Debug;
 use std::iter::FusedIterator;
 
-pub(crate) enum DrainInner<'a, K, V, const N: usize> {
+pub(crate) enum DrainInner<'a, K, V, const N: usize, A: Allocator = Global> {
     Heapless(HeaplessDrain<'a, K, V, N>),
-    Spilled(hash_map::Drain<'a, K, V>),
+    Spilled(spilled::Drain<'a, K, V, A>),
 }
 
-impl<K: Debug, V: Debug, const N: usize> Debug for DrainInner<'_, K, V, N> {
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for DrainInner<'_, K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Heapless(drain) => drain.fmt(f),
@@ -17,7 +18,7 @@ impl<K: Debug, V: Debug, const N: usize> Debug for DrainInner<'_, K, V, N> {
     }
 }
 
-impl<'a, K, V, const N: usize> Iterator for DrainInner<'a, K, V, N> {
+impl<'a, K, V, const N: usize, A: Allocator> Iterator for DrainInner<'a, K, V, N, A> {
     type Item = (K, V);
 
     #[inline]
@@ -53,7 +54,7 @@ impl<'a, K, V, const N: usize> Iterator for DrainInner<'a, K, V, N> {
         }
     }
 }
-impl<K, V, const N: usize> ExactSizeIterator for DrainInner<'_, K, V, N> {
+impl<K, V, const N: usize, A: Allocator> ExactSizeIterator for DrainInner<'_, K, V, N, A> {
     #[inline]
     fn len(&self) -> usize {
         match self {
@@ -62,10 +63,10 @@ impl<K, V, const N: usize> ExactSizeIterator for DrainInner<'_, K, V, N> {
         }
     }
 }
-impl<K, V, const N: usize> FusedIterator for DrainInner<'_, K, V, N> {}
+impl<K, V, const N: usize, A: Allocator> FusedIterator for DrainInner<'_, K, V, N, A> {}
 
 pub(crate) struct HeaplessDrain<'a, K, V, const N: usize> {
-    pub(crate) base: &'a mut heapless::Vec<(K, V), N>,
+    pub(crate) base: &'a mut InlineVec<(K, V), N>,
 }
 
 impl<K: Debug, V: Debug, const N: usize> Debug for HeaplessDrain<'_, K, V, N> {
@@ -1,11 +1,9 @@
+use crate::base::spilled::{self, Allocator, Global};
 use crate::base::MapImpl;
-use std::collections::hash_map::{
-    OccupiedEntry as HashMapOccupiedEntry, VacantEntry as HashMapVacantEntry,
-};
+use crate::utils::{checked_assert, unreachable_unchecked, unwrap_unchecked};
 use std::fmt;
 use std::fmt::Debug;
 use std::hash::Hash;
-use std::hint::unreachable_unchecked;
 
 /// A view into a single entry in a map, which may either be vacant or occupied.
 ///
@@ -13,14 +11,14 @@ use std::hint::unreachable_unchecked;
 ///
 /// [`entry`]: crate::CompactMap::entry
 /// [`CompactMap`]: crate::CompactMap
-pub enum Entry<'a, K: 'a, V: 'a, const N: usize> {
+pub enum Entry<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
     /// An occupied entry.
-    Occupied(OccupiedEntry<'a, K, V, N>),
+    Occupied(OccupiedEntry<'a, K, V, N, A>),
     /// A vacant entry.
-    Vacant(VacantEntry<'a, K, V, N>),
+    Vacant(VacantEntry<'a, K, V, N, A>),
 }
 
-impl<K: Debug, V: Debug, const N: usize> Debug for Entry<'_, K, V, N> {
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for Entry<'_, K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Entry::Vacant(ref v) => f.debug_tuple("Entry").field(v).finish(),
@@ -31,14 +29,16 @@ impl<K: Debug, V: Debug, const N: usize> Debug for Entry<'_, K, V, N> {
 
 /// A view into an occupied entry in a `CompactMap`.
 /// It is part of the [`Entry`] enum.
-pub enum OccupiedEntry<'a, K: 'a, V: 'a, const N: usize> {
-    /// An entry in the heapless state.
-    Heapless(HeaplessEntry<'a, K, V, N>),
-    /// An entry in the spilled state.
-    Spilled(HashMapOccupiedEntry<'a, K, V>),
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
+    pub(crate) inner: OccupiedEntryInner<'a, K, V, N, A>,
 }
 
-impl<K: Debug, V: Debug, const N: usize> Debug for OccupiedEntry<'_, K, V, N> {
+pub(crate) enum OccupiedEntryInner<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
+    Heapless(HeaplessEntry<'a, K, V, N, A>),
+    Spilled(spilled::OccupiedEntry<'a, K, V, A>),
+}
+
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for OccupiedEntry<'_, K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("OccupiedEntry")
             .field("key", self.key())
@@ -49,14 +49,16 @@ impl<K: Debug, V: Debug, const N: usize> Debug for OccupiedEntry<'_, K, V, N> {
 
 /// A view into a vacant entry in a `CompactMap`.
 /// It is part of the [`Entry`] enum.
-pub enum VacantEntry<'a, K: 'a, V: 'a, const N: usize> {
-    /// An entry in the heapless state.
-    Heapless(HeaplessEntry<'a, K, V, N>),
-    /// An entry in the spilled state.
-    Spilled(HashMapVacantEntry<'a, K, V>),
+pub struct VacantEntry<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
+    pub(crate) inner: VacantEntryInner<'a, K, V, N, A>,
+}
+
+pub(crate) enum VacantEntryInner<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
+    Heapless(HeaplessEntry<'a, K, V, N, A>),
+    Spilled(spilled::VacantEntry<'a, K, V, A>),
 }
 
-impl<K: Debug, V, const N: usize> Debug for VacantEntry<'_, K, V, N> {
+impl<K: Debug, V, const N: usize, A: Allocator> Debug for VacantEntry<'_, K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_tuple("VacantEntry").field(self.key()).finish()
     }
@@ -66,15 +68,15 @@ impl<K: Debug, V, const N: usize> Debug for VacantEntry<'_, K, V, N> {
 ///
 /// Contains the occupied entry, and the value that was not inserted.
 #[cfg(feature = "map_try_insert")]
-pub struct OccupiedError<'a, K: 'a, V: 'a, const N: usize> {
+pub struct OccupiedError<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
     /// The entry in the map that was already occupied.
-    pub entry: OccupiedEntry<'a, K, V, N>,
+    pub entry: OccupiedEntry<'a, K, V, N, A>,
     /// The value which was not inserted, because the entry was already occupied.
     pub value: V,
 }
 
 #[cfg(feature = "map_try_insert")]
-impl<K: Debug, V: Debug, const N: usize> Debug for OccupiedError<'_, K, V, N> {
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for OccupiedError<'_, K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("OccupiedError")
             .field("key", self.entry.key())
@@ -85,7 +87,9 @@ impl<K: Debug, V: Debug, const N: usize> Debug for OccupiedError<'_, K, V, N> {
 }
 
 #[cfg(feature = "map_try_insert")]
-impl<'a, K: Debug, V: Debug, const N: usize> fmt::Display for OccupiedError<'a, K, V, N> {
+impl<'a, K: Debug, V: Debug, const N: usize, A: Allocator> fmt::Display
+    for OccupiedError<'a, K, V, N, A>
+{
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -98,22 +102,23 @@ impl<'a, K: Debug, V: Debug, const N: usize> fmt::Display for OccupiedError<'a,
 }
 
 #[cfg(feature = "map_try_insert")]
-impl<'a, K: Debug, V: Debug, const N: usize> std::error::Error for OccupiedError<'a, K, V, N> {
+impl<'a, K: Debug, V: Debug, const N: usize, A: Allocator> std::error::Error
+    for OccupiedError<'a, K, V, N, A>
+{
     #[allow(deprecated)]
     fn description(&self) -> &str {
         "key already exists"
     }
 }
 
-/// A view into an entry in a `CompactMap`.
-/// It is part of the [`Entry`] enum.
-pub struct HeaplessEntry<'a, K: 'a, V: 'a, const N: usize> {
+/// An entry of a `CompactMap` in the heapless state.
+pub(crate) struct HeaplessEntry<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
     pub(crate) index: usize,
     pub(crate) key: Option<K>,
-    pub(crate) inner: &'a mut MapImpl<K, V, N>,
+    pub(crate) inner: &'a mut MapImpl<K, V, N, A>,
 }
 
-impl<'a, K, V, const N: usize> Entry<'a, K, V, N>
+impl<'a, K, V, const N: usize, A: Allocator + Clone> Entry<'a, K, V, N, A>
 where
     K: Eq + Hash,
 {
@@ -193,6 +198,38 @@ where
         }
     }
 
+    /// Ensures a value is in the entry by inserting the result of the fallible default
+    /// function if empty, and returns a mutable reference to the value in the entry.
+    ///
+    /// If the function fails, its error is returned and the map is left unchanged;
+    /// in particular, it doesn't spill.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<&str, u32, 1> = CompactMap::from([("a", 1)]);
+    ///
+    /// let parsed = map.entry("b").or_try_insert_with(|| "x".parse::<u32>());
+    /// assert!(parsed.is_err());
+    /// assert!(!map.spilled());
+    ///
+    /// let parsed = map.entry("b").or_try_insert_with(|| "2".parse::<u32>());
+    /// assert_eq!(parsed, Ok(&mut 2));
+    /// assert!(map.spilled());
+    /// ```
+    #[inline]
+    pub fn or_try_insert_with<E, F>(self, default: F) -> Result<&'a mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(default()?)),
+        }
+    }
+
     /// Sets the value of the entry, and returns an `OccupiedEntry`.
     ///
     /// # Examples
@@ -208,7 +245,7 @@ where
     #[cfg_attr(docsrs, doc(cfg(feature = "entry_insert")))]
     #[cfg(feature = "entry_insert")]
     #[inline]
-    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, N> {
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, N, A> {
         match self {
             Entry::Occupied(mut entry) => {
                 entry.insert(value);
@@ -219,7 +256,7 @@ where
     }
 }
 
-impl<'a, K, V, const N: usize> Entry<'a, K, V, N> {
+impl<'a, K, V, const N: usize, A: Allocator> Entry<'a, K, V, N, A> {
     /// Returns a reference to this entry's key.
     ///
     /// # Examples
@@ -271,9 +308,56 @@ impl<'a, K, V, const N: usize> Entry<'a, K, V, N> {
             Entry::Vacant(entry) => Entry::Vacant(entry),
         }
     }
+
+    /// Returns `true` if the map is in the heapless state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 2> = CompactMap::from([(1, 1), (2, 2)]);
+    /// assert!(map.entry(1).is_inline());
+    ///
+    /// map.insert(3, 3);
+    /// assert!(!map.entry(1).is_inline());
+    /// ```
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        match self {
+            Entry::Occupied(entry) => entry.is_inline(),
+            Entry::Vacant(entry) => entry.is_inline(),
+        }
+    }
+
+    /// Returns `true` if inserting a value through this entry would spill the map
+    /// into a `HashMap`.
+    ///
+    /// That is, if the entry is vacant and the heapless map is full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 2> = CompactMap::from([(1, 1), (2, 2)]);
+    /// assert!(!map.entry(1).will_spill_on_insert());
+    /// assert!(map.entry(3).will_spill_on_insert());
+    ///
+    /// map.entry(3).or_insert(3);
+    /// assert!(map.spilled());
+    /// assert!(!map.entry(4).will_spill_on_insert());
+    /// ```
+    #[inline]
+    pub fn will_spill_on_insert(&self) -> bool {
+        match self {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => entry.will_spill_on_insert(),
+        }
+    }
 }
 
-impl<'a, K, V: Default, const N: usize> Entry<'a, K, V, N>
+impl<'a, K, V: Default, const N: usize, A: Allocator + Clone> Entry<'a, K, V, N, A>
 where
     K: Eq + Hash,
 {
@@ -301,7 +385,26 @@ where
     }
 }
 
-impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
+impl<'a, K, V, const N: usize, A: Allocator> OccupiedEntry<'a, K, V, N, A> {
+    /// Returns `true` if the map is in the heapless state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::{CompactMap, Entry};
+    ///
+    /// let mut map: CompactMap<&str, u32, 16> = CompactMap::new();
+    /// map.entry("poneyland").or_insert(12);
+    ///
+    /// if let Entry::Occupied(o) = map.entry("poneyland") {
+    ///     assert!(o.is_inline());
+    /// }
+    /// ```
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.inner, OccupiedEntryInner::Heapless(_))
+    }
+
     /// Gets a reference to the key in the entry.
     ///
     /// # Examples
@@ -315,9 +418,9 @@ impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn key(&self) -> &K {
-        match self {
-            Self::Heapless(entry) => entry.key(),
-            Self::Spilled(entry) => entry.key(),
+        match &self.inner {
+            OccupiedEntryInner::Heapless(entry) => entry.key(),
+            OccupiedEntryInner::Spilled(entry) => entry.key(),
         }
     }
 
@@ -340,17 +443,18 @@ impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn remove_entry(self) -> (K, V) {
-        match self {
-            Self::Heapless(entry) => {
+        match self.inner {
+            OccupiedEntryInner::Heapless(entry) => {
+                let index = entry.checked_index();
                 // SAFETY: the entry is occupied
                 unsafe {
                     entry
                         .inner
                         .as_heapless_mut_unchecked()
-                        .swap_remove_unchecked(entry.index)
+                        .swap_remove_unchecked(index)
                 }
             }
-            Self::Spilled(entry) => entry.remove_entry(),
+            OccupiedEntryInner::Spilled(entry) => entry.remove_entry(),
         }
     }
 
@@ -370,12 +474,12 @@ impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn get(&self) -> &V {
-        match self {
-            Self::Heapless(entry) => {
+        match &self.inner {
+            OccupiedEntryInner::Heapless(entry) => {
                 // SAFETY: the entry is occupied
                 unsafe { entry.get_unchecked() }
             }
-            Self::Spilled(entry) => entry.get(),
+            OccupiedEntryInner::Spilled(entry) => entry.get(),
         }
     }
 
@@ -407,12 +511,12 @@ impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn get_mut(&mut self) -> &mut V {
-        match self {
-            Self::Heapless(entry) => {
+        match &mut self.inner {
+            OccupiedEntryInner::Heapless(entry) => {
                 // SAFETY: the entry is occupied
                 unsafe { entry.get_unchecked_mut() }
             }
-            Self::Spilled(entry) => entry.get_mut(),
+            OccupiedEntryInner::Spilled(entry) => entry.get_mut(),
         }
     }
 
@@ -440,12 +544,15 @@ impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn into_mut(self) -> &'a mut V {
-        match self {
-            Self::Heapless(HeaplessEntry { index, inner, .. }) => {
+        match self.inner {
+            OccupiedEntryInner::Heapless(HeaplessEntry { index, inner, .. }) => {
                 // SAFETY: the entry is occupied
-                unsafe { &mut inner.as_heapless_mut_unchecked().get_unchecked_mut(index).1 }
+                let vec = unsafe { inner.as_heapless_mut_unchecked() };
+                checked_assert!(index < vec.len());
+                // SAFETY: the entry is occupied
+                unsafe { &mut vec.get_unchecked_mut(index).1 }
             }
-            Self::Spilled(entry) => entry.into_mut(),
+            OccupiedEntryInner::Spilled(entry) => entry.into_mut(),
         }
     }
 
@@ -467,12 +574,12 @@ impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn insert(&mut self, value: V) -> V {
-        match self {
-            Self::Heapless(entry) => {
+        match &mut self.inner {
+            OccupiedEntryInner::Heapless(entry) => {
                 // SAFETY: the entry is occupied
                 unsafe { std::mem::replace(entry.get_unchecked_mut(), value) }
             }
-            Self::Spilled(entry) => entry.insert(value),
+            OccupiedEntryInner::Spilled(entry) => entry.insert(value),
         }
     }
 
@@ -494,23 +601,24 @@ impl<'a, K, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn remove(self) -> V {
-        match self {
-            Self::Heapless(entry) => {
+        match self.inner {
+            OccupiedEntryInner::Heapless(entry) => {
+                let index = entry.checked_index();
                 // SAFETY: the entry is occupied
                 unsafe {
                     entry
                         .inner
                         .as_heapless_mut_unchecked()
-                        .swap_remove_unchecked(entry.index)
+                        .swap_remove_unchecked(index)
                         .1
                 }
             }
-            Self::Spilled(entry) => entry.remove(),
+            OccupiedEntryInner::Spilled(entry) => entry.remove(),
         }
     }
 }
 
-impl<'a, K: Clone, V, const N: usize> OccupiedEntry<'a, K, V, N> {
+impl<'a, K: Clone, V, const N: usize, A: Allocator> OccupiedEntry<'a, K, V, N, A> {
     /// Replaces the entry, returning the old key and value. The new key in the hash map will be
     /// the key used to create this entry.
     ///
@@ -535,20 +643,21 @@ impl<'a, K: Clone, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     #[cfg(feature = "map_entry_replace")]
     #[inline]
     pub fn replace_entry(self, value: V) -> (K, V) {
-        match self {
-            Self::Heapless(mut entry) => {
+        match self.inner {
+            OccupiedEntryInner::Heapless(mut entry) => {
                 let key = entry.key_owned();
                 // SAFETY: it is in heapless state
                 let vec = unsafe { entry.inner.as_heapless_mut_unchecked() };
+                checked_assert!(entry.index < vec.len());
                 // SAFETY: the entry is occupied
                 let (old_key, old_value) = unsafe { vec.swap_remove_unchecked(entry.index) };
                 // SAFETY: We just removed an element, so the push is safe
                 unsafe {
-                    vec.push((key, value)).unwrap_unchecked();
+                    unwrap_unchecked(vec.push((key, value)).ok());
                 }
                 (old_key, old_value)
             }
-            Self::Spilled(entry) => entry.replace_entry(value),
+            OccupiedEntryInner::Spilled(entry) => entry.replace_entry(value),
         }
     }
 
@@ -580,25 +689,74 @@ impl<'a, K: Clone, V, const N: usize> OccupiedEntry<'a, K, V, N> {
     #[cfg(feature = "map_entry_replace")]
     #[inline]
     pub fn replace_key(self) -> K {
-        match self {
-            Self::Heapless(mut entry) => {
+        match self.inner {
+            OccupiedEntryInner::Heapless(mut entry) => {
                 let key = entry.key_owned();
                 // SAFETY: it is in heapless state
                 let vec = unsafe { entry.inner.as_heapless_mut_unchecked() };
+                checked_assert!(entry.index < vec.len());
                 // SAFETY: the entry is occupied
                 let (old_key, value) = unsafe { vec.swap_remove_unchecked(entry.index) };
+                checked_assert!(!vec.is_full());
                 // SAFETY: We just removed an element, so the push is safe
                 unsafe {
                     vec.push_unchecked((key, value));
                 }
                 old_key
             }
-            Self::Spilled(entry) => entry.replace_key(),
+            OccupiedEntryInner::Spilled(entry) => entry.replace_key(),
         }
     }
 }
 
-impl<'a, K: 'a, V: 'a, const N: usize> VacantEntry<'a, K, V, N> {
+impl<'a, K: 'a, V: 'a, const N: usize, A: Allocator> VacantEntry<'a, K, V, N, A> {
+    /// Returns `true` if the map is in the heapless state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::{CompactMap, Entry};
+    ///
+    /// let mut map: CompactMap<&str, u32, 16> = CompactMap::new();
+    ///
+    /// if let Entry::Vacant(v) = map.entry("poneyland") {
+    ///     assert!(v.is_inline());
+    /// }
+    /// ```
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.inner, VacantEntryInner::Heapless(_))
+    }
+
+    /// Returns `true` if inserting a value through this entry would spill the map
+    /// into a `HashMap`, because the heapless map is full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::{CompactMap, Entry};
+    ///
+    /// let mut map: CompactMap<&str, u32, 1> = CompactMap::new();
+    ///
+    /// if let Entry::Vacant(v) = map.entry("a") {
+    ///     assert!(!v.will_spill_on_insert());
+    ///     v.insert(1);
+    /// }
+    /// if let Entry::Vacant(v) = map.entry("b") {
+    ///     assert!(v.will_spill_on_insert());
+    /// }
+    /// ```
+    #[inline]
+    pub fn will_spill_on_insert(&self) -> bool {
+        match &self.inner {
+            VacantEntryInner::Heapless(entry) => {
+                // SAFETY: HeaplessEntry only constructed when the in heapless state
+                unsafe { entry.inner.as_heapless_unchecked() }.is_full()
+            }
+            VacantEntryInner::Spilled(_) => false,
+        }
+    }
+
     /// Gets a reference to the key that would be used when inserting a value
     /// through the `VacantEntry`.
     ///
@@ -612,12 +770,12 @@ impl<'a, K: 'a, V: 'a, const N: usize> VacantEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn key(&self) -> &K {
-        match self {
-            Self::Heapless(entry) => {
+        match &self.inner {
+            VacantEntryInner::Heapless(entry) => {
                 // SAFETY: vacant entry always has a key
                 unsafe { entry.key_unchecked() }
             }
-            Self::Spilled(entry) => entry.key(),
+            VacantEntryInner::Spilled(entry) => entry.key(),
         }
     }
 
@@ -636,17 +794,17 @@ impl<'a, K: 'a, V: 'a, const N: usize> VacantEntry<'a, K, V, N> {
     /// ```
     #[inline]
     pub fn into_key(self) -> K {
-        match self {
-            Self::Heapless(entry) => {
+        match self.inner {
+            VacantEntryInner::Heapless(entry) => {
                 // SAFETY: vacant entry always has a key
-                unsafe { entry.key.unwrap_unchecked() }
+                unsafe { unwrap_unchecked(entry.key) }
             }
-            Self::Spilled(entry) => entry.into_key(),
+            VacantEntryInner::Spilled(entry) => entry.into_key(),
         }
     }
 }
 
-impl<'a, K: 'a, V: 'a, const N: usize> VacantEntry<'a, K, V, N>
+impl<'a, K: 'a, V: 'a, const N: usize, A: Allocator + Clone> VacantEntry<'a, K, V, N, A>
 where
     K: Eq + Hash,
 {
@@ -667,14 +825,15 @@ where
     /// ```
     #[inline]
     pub fn insert(self, value: V) -> &'a mut V {
-        match self {
-            Self::Heapless(HeaplessEntry { index, key, inner }) => {
+        match self.inner {
+            VacantEntryInner::Heapless(HeaplessEntry { index, key, inner }) => {
                 // SAFETY: vacant entry always has a key
-                let k = unsafe { key.unwrap_unchecked() };
+                let k = unsafe { unwrap_unchecked(key) };
                 // SAFETY: HeaplessEntry only constructed when the in heapless state
                 let vec_is_full = unsafe { inner.as_heapless_unchecked().is_full() };
                 if !vec_is_full {
                     let vec = unsafe { inner.as_heapless_mut_unchecked() };
+                    checked_assert!(!vec.is_full());
                     // SAFETY: We just checked that the vec is not full
                     unsafe { vec.push_unchecked((k, value)) };
                     debug_assert!(vec.len() - 1 == index);
@@ -686,7 +845,7 @@ where
                     map.unwrap().entry(k).or_insert(value)
                 }
             }
-            Self::Spilled(entry) => entry.insert(value),
+            VacantEntryInner::Spilled(entry) => entry.insert(value),
         }
     }
 
@@ -708,34 +867,44 @@ where
     #[cfg_attr(docsrs, doc(cfg(feature = "entry_insert")))]
     #[cfg(feature = "entry_insert")]
     #[inline]
-    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, N> {
-        match self {
-            Self::Heapless(HeaplessEntry { index, key, inner }) => {
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, N, A> {
+        match self.inner {
+            VacantEntryInner::Heapless(HeaplessEntry { index, key, inner }) => {
                 // SAFETY: vacant entry always has a key
-                let k = unsafe { key.unwrap_unchecked() };
+                let k = unsafe { unwrap_unchecked(key) };
                 // SAFETY: HeaplessEntry only constructed when the in heapless state
                 let vec = unsafe { inner.as_heapless_mut_unchecked() };
                 if !vec.is_full() {
+                    checked_assert!(!vec.is_full());
                     // SAFETY: We just checked that the vec is not full
                     unsafe { vec.push_unchecked((k, value)) };
                     debug_assert!(vec.len() - 1 == index);
-                    OccupiedEntry::Heapless(HeaplessEntry {
-                        index,
-                        key: None,
-                        inner,
-                    })
+                    OccupiedEntry {
+                        inner: OccupiedEntryInner::Heapless(HeaplessEntry {
+                            index,
+                            key: None,
+                            inner,
+                        }),
+                    }
                 } else {
                     // SAFETY: current in heapless
                     let map = unsafe { inner.try_spill(1) };
-                    OccupiedEntry::Spilled(map.unwrap().entry(k).insert_entry(value))
+                    OccupiedEntry {
+                        inner: OccupiedEntryInner::Spilled(spilled::insert_entry(
+                            map.unwrap().entry(k),
+                            value,
+                        )),
+                    }
                 }
             }
-            Self::Spilled(entry) => OccupiedEntry::Spilled(entry.insert_entry(value)),
+            VacantEntryInner::Spilled(entry) => OccupiedEntry {
+                inner: OccupiedEntryInner::Spilled(spilled::vacant_insert_entry(entry, value)),
+            },
         }
     }
 }
 
-impl<K, V, const N: usize> HeaplessEntry<'_, K, V, N> {
+impl<K, V, const N: usize, A: Allocator> HeaplessEntry<'_, K, V, N, A> {
     #[inline]
     fn key(&self) -> &K {
         match self.key {
@@ -746,7 +915,7 @@ impl<K, V, const N: usize> HeaplessEntry<'_, K, V, N> {
                     &self
                         .inner
                         .as_heapless_unchecked()
-                        .get_unchecked(self.index)
+                        .get_unchecked(self.checked_index())
                         .0
                 }
             }
@@ -772,7 +941,7 @@ impl<K, V, const N: usize> HeaplessEntry<'_, K, V, N> {
         &self
             .inner
             .as_heapless_unchecked()
-            .get_unchecked(self.index)
+            .get_unchecked(self.checked_index())
             .1
     }
 
@@ -781,26 +950,39 @@ impl<K, V, const N: usize> HeaplessEntry<'_, K, V, N> {
     /// Must be called when the entry is occupied.
     #[inline]
     unsafe fn get_unchecked_mut(&mut self) -> &mut V {
+        let index = self.checked_index();
         &mut self
             .inner
             .as_heapless_mut_unchecked()
-            .get_unchecked_mut(self.index)
+            .get_unchecked_mut(index)
             .1
     }
+
+    /// The entry's index, asserted to be in bounds with the `checked` feature.
+    #[inline(always)]
+    fn checked_index(&self) -> usize {
+        #[cfg(feature = "checked")]
+        assert!(
+            self.index < unsafe { self.inner.as_heapless_unchecked() }.len(),
+            "entry index out of bounds"
+        );
+        self.index
+    }
 }
 
 #[cfg(feature = "map_entry_replace")]
-impl<K: Clone, V, const N: usize> HeaplessEntry<'_, K, V, N> {
+impl<K: Clone, V, const N: usize, A: Allocator> HeaplessEntry<'_, K, V, N, A> {
     #[inline]
     fn key_owned(&mut self) -> K {
         match self.key.take() {
             Some(k) => k,
             None => {
+                let index = self.checked_index();
                 // SAFETY: vacant entry always has a key
                 unsafe {
                     self.inner
                         .as_heapless_mut_unchecked()
-                        .get_unchecked(self.index)
+                        .get_unchecked(index)
                         .0
                         .clone()
                 }
@@ -1,20 +1,21 @@
-use std::collections::hash_map;
+use crate::base::inline_vec::InlineVec;
+use crate::base::spilled::{self, Allocator, Global};
 use std::iter::FusedIterator;
 
 #[must_use = "iterators are lazy and do nothing unless consumed"]
-pub(crate) enum ExtractIfInner<'a, K, V, F, const N: usize>
+pub(crate) enum ExtractIfInner<'a, K, V, F, const N: usize, A: Allocator = Global>
 where
     F: FnMut(&K, &mut V) -> bool,
 {
     Heapless {
-        base: &'a mut heapless::Vec<(K, V), N>,
+        base: &'a mut InlineVec<(K, V), N>,
         next: usize,
         pred: F,
     },
-    Spilled(hash_map::ExtractIf<'a, K, V, F>),
+    Spilled(spilled::ExtractIf<'a, K, V, F, A>),
 }
 
-impl<K, V, F, const N: usize> Iterator for ExtractIfInner<'_, K, V, F, N>
+impl<K, V, F, const N: usize, A: Allocator> Iterator for ExtractIfInner<'_, K, V, F, N, A>
 where
     F: FnMut(&K, &mut V) -> bool,
 {
@@ -30,7 +31,8 @@ where
                         pred(&elem.0, &mut elem.1)
                     };
                     if cond {
-                        return Some(base.swap_remove(*next));
+                        // Safety: `next` is in bounds
+                        return Some(unsafe { base.swap_remove_unchecked(*next) });
                     } else {
                         *next += 1;
                     }
@@ -49,7 +51,7 @@ where
     }
 }
 
-impl<K, V, F, const N: usize> FusedIterator for ExtractIfInner<'_, K, V, F, N> where
+impl<K, V, F, const N: usize, A: Allocator> FusedIterator for ExtractIfInner<'_, K, V, F, N, A> where
     F: FnMut(&K, &mut V) -> bool
 {
 }
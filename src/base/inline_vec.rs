@@ -0,0 +1,264 @@
+use crate::utils::checked_assert;
+use std::fmt;
+use std::fmt::Debug;
+use std::iter::FusedIterator;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::num::NonZeroUsize;
+use std::ops::{Deref, DerefMut};
+use std::{ptr, slice};
+
+/// A fixed capacity vector, like `heapless::Vec`, whose length leaves a niche.
+///
+/// The length is stored plus one in a `NonZeroUsize`, so `MapImpl` can keep its tag in
+/// the zero value and is no bigger than its inline storage.
+pub(crate) struct InlineVec<T, const N: usize> {
+    buffer: [MaybeUninit<T>; N],
+    len_plus_one: NonZeroUsize,
+}
+
+impl<T, const N: usize> InlineVec<T, N> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            // Safety: an array of `MaybeUninit` needs no initialization
+            buffer: unsafe { MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init() },
+            len_plus_one: NonZeroUsize::MIN,
+        }
+    }
+
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.len_plus_one.get() - 1
+    }
+
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline(always)]
+    pub const fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    #[inline(always)]
+    pub fn as_ptr(&self) -> *const T {
+        self.buffer.as_ptr() as *const T
+    }
+
+    #[inline(always)]
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.buffer.as_mut_ptr() as *mut T
+    }
+
+    /// # Safety
+    ///
+    /// `new_len` must be at most `N`, and the first `new_len` elements must be initialized.
+    #[inline(always)]
+    pub unsafe fn set_len(&mut self, new_len: usize) {
+        checked_assert!(new_len <= N);
+        // Safety: `new_len <= N`, and an array can't hold `usize::MAX` elements
+        self.len_plus_one = unsafe { NonZeroUsize::new_unchecked(new_len + 1) };
+    }
+
+    #[inline]
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            Err(item)
+        } else {
+            // Safety: we just checked the length
+            unsafe { self.push_unchecked(item) };
+            Ok(())
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The vector must not be full.
+    #[inline]
+    pub unsafe fn push_unchecked(&mut self, item: T) {
+        checked_assert!(!self.is_full());
+        let len = self.len();
+        unsafe {
+            // Safety: guaranteed by the caller
+            self.buffer.get_unchecked_mut(len).write(item);
+            self.set_len(len + 1);
+        }
+    }
+
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            let len = self.len() - 1;
+            unsafe {
+                // Safety: the element at `len` is initialized, and no longer counted
+                self.set_len(len);
+                Some(self.buffer.get_unchecked(len).assume_init_read())
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `index` must be in bounds.
+    #[inline]
+    pub unsafe fn swap_remove_unchecked(&mut self, index: usize) -> T {
+        let len = self.len();
+        checked_assert!(index < len);
+        unsafe {
+            // Safety: both `index` and `len - 1` are in bounds
+            let base = self.as_mut_ptr();
+            let item = ptr::read(base.add(index));
+            ptr::copy(base.add(len - 1), base.add(index), 1);
+            self.set_len(len - 1);
+            item
+        }
+    }
+
+    #[inline]
+    pub fn retain_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let len = self.len();
+        // Elements are shifted down over the removed ones. If `f` or a drop panics, the
+        // length only covers the elements kept so far, and the rest are leaked.
+        unsafe { self.set_len(0) };
+        let base = self.as_mut_ptr();
+        let mut kept = 0;
+        for i in 0..len {
+            unsafe {
+                // Safety: `i < len`, and every element is visited once
+                let item = base.add(i);
+                if f(&mut *item) {
+                    ptr::copy(item, base.add(kept), 1);
+                    kept += 1;
+                    self.set_len(kept);
+                } else {
+                    ptr::drop_in_place(item);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        let len = self.len();
+        unsafe {
+            // Safety: the first `len` elements are initialized, and no longer counted
+            self.set_len(0);
+            ptr::drop_in_place(slice::from_raw_parts_mut(self.as_mut_ptr(), len));
+        }
+    }
+
+    /// Moves the elements into a `heapless::Vec` of the same capacity.
+    pub fn into_heapless(self) -> heapless::Vec<T, N> {
+        let this = ManuallyDrop::new(self);
+        let mut vec = heapless::Vec::new();
+        unsafe {
+            // Safety: `this` won't be dropped, and the two buffers can't overlap
+            ptr::copy_nonoverlapping(this.as_ptr(), vec.as_mut_ptr(), this.len());
+            vec.set_len(this.len());
+        }
+        vec
+    }
+}
+
+impl<T, const N: usize> Deref for InlineVec<T, N> {
+    type Target = [T];
+
+    #[inline(always)]
+    fn deref(&self) -> &[T] {
+        // Safety: the first `len` elements are initialized
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len()) }
+    }
+}
+
+impl<T, const N: usize> DerefMut for InlineVec<T, N> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [T] {
+        // Safety: the first `len` elements are initialized
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len()) }
+    }
+}
+
+impl<T, const N: usize> Drop for InlineVec<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: Debug, const N: usize> Debug for InlineVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for InlineVec<T, N> {
+    /// # Panics
+    ///
+    /// Panics if the iterator yields more than `N` elements.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        for item in iter {
+            if vec.push(item).is_err() {
+                panic!("InlineVec::from_iter overflow");
+            }
+        }
+        vec
+    }
+}
+
+impl<T, const N: usize> IntoIterator for InlineVec<T, N> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+
+    #[inline]
+    fn into_iter(self) -> IntoIter<T, N> {
+        IntoIter { vec: self, next: 0 }
+    }
+}
+
+/// An owning iterator over an [`InlineVec`], front to back.
+pub(crate) struct IntoIter<T, const N: usize> {
+    // the elements before `next` are moved out, but still counted in the length
+    vec: InlineVec<T, N>,
+    next: usize,
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        if self.next < self.vec.len() {
+            // Safety: the element at `next` is initialized and not yet moved out
+            let item = unsafe { ptr::read(self.vec.as_ptr().add(self.next)) };
+            self.next += 1;
+            Some(item)
+        } else {
+            None
+        }
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.vec.len() - self.next;
+        (len, Some(len))
+    }
+}
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+impl<T, const N: usize> FusedIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        let (next, len) = (self.next, self.vec.len());
+        unsafe {
+            // Safety: the elements from `next` on are initialized and not yet moved out
+            self.vec.set_len(0);
+            let rest = self.vec.as_mut_ptr().add(next);
+            ptr::drop_in_place(slice::from_raw_parts_mut(rest, len - next));
+        }
+    }
+}
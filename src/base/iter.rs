@@ -1,4 +1,5 @@
-use std::collections::hash_map;
+use crate::base::inline_vec::InlineVec;
+use crate::base::spilled::{self, Allocator, Global};
 use std::fmt::Debug;
 use std::iter::FusedIterator;
 use std::{fmt, slice};
@@ -6,9 +7,9 @@ use std::{fmt, slice};
 pub(crate) enum IterInner<'a, K, V, const N: usize> {
     Heapless {
         next: usize,
-        vec: &'a heapless::Vec<(K, V), N>,
+        vec: &'a InlineVec<(K, V), N>,
     },
-    Spilled(hash_map::Iter<'a, K, V>),
+    Spilled(spilled::Iter<'a, K, V>),
 }
 
 impl<K, V, const N: usize> Clone for IterInner<'_, K, V, N> {
@@ -101,7 +102,7 @@ impl<'a, K, V, const N: usize> FusedIterator for IterInner<'a, K, V, N> {}
 
 pub(crate) enum IterMutInner<'a, K, V, const N: usize> {
     Heapless(slice::IterMut<'a, (K, V)>),
-    Spilled(hash_map::IterMut<'a, K, V>),
+    Spilled(spilled::IterMut<'a, K, V>),
 }
 
 impl<'a, K, V, const N: usize> Iterator for IterMutInner<'a, K, V, N> {
@@ -154,12 +155,12 @@ impl<K, V, const N: usize> ExactSizeIterator for IterMutInner<'_, K, V, N> {
 }
 impl<K, V, const N: usize> FusedIterator for IterMutInner<'_, K, V, N> {}
 
-pub(crate) enum IntoIterInner<K, V, const N: usize> {
-    Heapless(heapless::Vec<(K, V), N>),
-    Spilled(hash_map::IntoIter<K, V>),
+pub(crate) enum IntoIterInner<K, V, const N: usize, A: Allocator = Global> {
+    Heapless(InlineVec<(K, V), N>),
+    Spilled(spilled::IntoIter<K, V, A>),
 }
 
-impl<K: Debug, V: Debug, const N: usize> Debug for IntoIterInner<K, V, N> {
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for IntoIterInner<K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Heapless(vec) => f.debug_list().entries(vec.iter()).finish(),
@@ -168,7 +169,7 @@ impl<K: Debug, V: Debug, const N: usize> Debug for IntoIterInner<K, V, N> {
     }
 }
 
-impl<K, V, const N: usize> Iterator for IntoIterInner<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> Iterator for IntoIterInner<K, V, N, A> {
     type Item = (K, V);
 
     #[inline]
@@ -204,7 +205,7 @@ impl<K, V, const N: usize> Iterator for IntoIterInner<K, V, N> {
         }
     }
 }
-impl<K, V, const N: usize> ExactSizeIterator for IntoIterInner<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> ExactSizeIterator for IntoIterInner<K, V, N, A> {
     #[inline]
     fn len(&self) -> usize {
         match self {
@@ -213,4 +214,4 @@ impl<K, V, const N: usize> ExactSizeIterator for IntoIterInner<K, V, N> {
         }
     }
 }
-impl<K, V, const N: usize> FusedIterator for IntoIterInner<K, V, N> {}
+impl<K, V, const N: usize, A: Allocator> FusedIterator for IntoIterInner<K, V, N, A> {}
@@ -0,0 +1,110 @@
+// `Sealed` can't be named outside the crate, so its methods may take private types.
+#![allow(private_interfaces)]
+
+use crate::base::spilled::{Allocator, SpilledBox};
+#[cfg(feature = "equivalent")]
+use equivalent::Equivalent;
+#[cfg(not(feature = "equivalent"))]
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+/// A key type that lookups in a map with keys of type `K` accept.
+///
+/// Without the `equivalent` feature, these are the borrowed forms of `K`: every `Q` with
+/// `K: Borrow<Q>` and `Q: Hash + Eq`, as for std `HashMap`. With it, these are every
+/// `Q: Hash + Equivalent<K>`, which includes the borrowed forms.
+///
+/// This trait is sealed, and implemented for all of those types.
+pub trait Lookup<K>: Hash + sealed::Sealed<K> {}
+
+mod sealed {
+    use super::*;
+
+    /// Only [`matches`](Sealed::matches) differs between the two sets of key types, so
+    /// the spilled map is searched through its raw table with it.
+    pub trait Sealed<K>: Hash {
+        fn matches(&self, key: &K) -> bool;
+
+        #[inline]
+        fn get_spilled<'m, V, A: Allocator>(
+            &self,
+            map: &'m SpilledBox<K, V, A>,
+        ) -> Option<(&'m K, &'m V)> {
+            let hash = map.hasher().hash_one(self);
+            let (k, v) = map.raw_table().get(hash, |(k, _)| self.matches(k))?;
+            Some((k, v))
+        }
+
+        #[inline]
+        fn get_spilled_mut<'m, V, A: Allocator>(
+            &self,
+            map: &'m mut SpilledBox<K, V, A>,
+        ) -> Option<&'m mut V> {
+            let hash = map.hasher().hash_one(self);
+            let (_, v) = map
+                .raw_table_mut()
+                .get_mut(hash, |(k, _)| self.matches(k))?;
+            Some(v)
+        }
+
+        #[cfg(feature = "many_mut")]
+        #[inline]
+        fn get_many_spilled_mut<'m, V, A: Allocator, const M: usize>(
+            ks: [&Self; M],
+            map: &'m mut SpilledBox<K, V, A>,
+        ) -> Option<[&'m mut V; M]> {
+            let hashes = ks.map(|k| map.hasher().hash_one(k));
+            let entries = map
+                .raw_table_mut()
+                .get_many_mut(hashes, |i, (k, _)| ks[i].matches(k))?;
+            Some(entries.map(|(_, v)| v))
+        }
+
+        #[inline]
+        fn remove_spilled<V, A: Allocator>(&self, map: &mut SpilledBox<K, V, A>) -> Option<(K, V)> {
+            let hash = map.hasher().hash_one(self);
+            map.raw_table_mut()
+                .remove_entry(hash, |(k, _)| self.matches(k))
+        }
+    }
+}
+
+#[cfg(not(feature = "equivalent"))]
+impl<K, Q> Lookup<K> for Q
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+}
+
+#[cfg(not(feature = "equivalent"))]
+impl<K, Q> sealed::Sealed<K> for Q
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    #[inline]
+    fn matches(&self, key: &K) -> bool {
+        key.borrow() == self
+    }
+}
+
+#[cfg(feature = "equivalent")]
+impl<K, Q> Lookup<K> for Q
+where
+    K: Eq + Hash,
+    Q: Hash + Equivalent<K> + ?Sized,
+{
+}
+
+#[cfg(feature = "equivalent")]
+impl<K, Q> sealed::Sealed<K> for Q
+where
+    K: Eq + Hash,
+    Q: Hash + Equivalent<K> + ?Sized,
+{
+    #[inline]
+    fn matches(&self, key: &K) -> bool {
+        self.equivalent(key)
+    }
+}
@@ -0,0 +1,184 @@
+//! The map that entries spill into once they don't fit inline.
+//!
+//! This is a `hashbrown::HashMap`, allocated through `A`, whose raw table the map reaches
+//! into for the operations std has no API for.
+
+use crate::utils::checked_assert;
+use hashbrown::raw::{Bucket, RawIter, RawTable};
+use std::hash::Hash;
+
+pub(crate) use hashbrown::hash_map;
+
+pub use allocator_api2::alloc::{Allocator, Global};
+
+pub type Map<K, V, S, A> = hashbrown::HashMap<K, V, S, A>;
+
+/// The spilled map with the hasher that `MapImpl` uses.
+pub(crate) type HashMap<K, V, A> = Map<K, V, std::collections::hash_map::RandomState, A>;
+
+pub(crate) type SpilledBox<K, V, A> = allocator_api2::boxed::Box<HashMap<K, V, A>, A>;
+
+pub(crate) use hash_map::{Iter, IterMut};
+pub(crate) type IntoIter<K, V, A> = hash_map::IntoIter<K, V, A>;
+pub(crate) type Drain<'a, K, V, A> = hash_map::Drain<'a, K, V, A>;
+#[cfg(feature = "extract_if")]
+pub(crate) type ExtractIf<'a, K, V, F, A> = hash_map::ExtractIf<'a, K, V, F, A>;
+#[cfg(feature = "entry_insert")]
+pub(crate) type Entry<'a, K, V, A> =
+    hash_map::Entry<'a, K, V, std::collections::hash_map::RandomState, A>;
+pub(crate) type OccupiedEntry<'a, K, V, A> =
+    hash_map::OccupiedEntry<'a, K, V, std::collections::hash_map::RandomState, A>;
+pub(crate) type VacantEntry<'a, K, V, A> =
+    hash_map::VacantEntry<'a, K, V, std::collections::hash_map::RandomState, A>;
+
+#[inline]
+pub(crate) fn with_capacity_in<K, V, A: Allocator>(capacity: usize, alloc: A) -> HashMap<K, V, A> {
+    hashbrown::HashMap::with_capacity_and_hasher_in(capacity, Default::default(), alloc)
+}
+
+#[inline]
+pub(crate) fn with_capacity_and_hasher_in<K, V, S, A: Allocator>(
+    capacity: usize,
+    hash_builder: S,
+    alloc: A,
+) -> Map<K, V, S, A> {
+    hashbrown::HashMap::with_capacity_and_hasher_in(capacity, hash_builder, alloc)
+}
+
+#[inline]
+pub(crate) fn allocator<K, V, A: Allocator>(map: &SpilledBox<K, V, A>) -> &A {
+    map.allocator()
+}
+
+/// Boxes `map` in its own allocator, so a spilled map allocates nothing elsewhere.
+#[inline]
+pub(crate) fn boxed<K, V, A: Allocator + Clone>(map: HashMap<K, V, A>) -> SpilledBox<K, V, A> {
+    let alloc = map.allocator().clone();
+    allocator_api2::boxed::Box::new_in(map, alloc)
+}
+
+#[inline]
+pub(crate) fn unbox<K, V, A: Allocator>(map: SpilledBox<K, V, A>) -> HashMap<K, V, A> {
+    allocator_api2::boxed::Box::into_inner(map)
+}
+
+#[cfg(feature = "entry_insert")]
+#[inline]
+pub(crate) fn insert_entry<K: Hash, V, A: Allocator>(
+    entry: Entry<'_, K, V, A>,
+    value: V,
+) -> OccupiedEntry<'_, K, V, A> {
+    entry.insert(value)
+}
+
+#[cfg(feature = "entry_insert")]
+#[inline]
+pub(crate) fn vacant_insert_entry<K: Hash, V, A: Allocator>(
+    entry: VacantEntry<'_, K, V, A>,
+    value: V,
+) -> OccupiedEntry<'_, K, V, A> {
+    // hashbrown only has this on `Entry`
+    hash_map::Entry::Vacant(entry).insert(value)
+}
+
+/// Removes the entry at position `index` in the iteration order of `map`.
+#[inline]
+pub(crate) fn remove_nth<K, V, A: Allocator>(
+    map: &mut SpilledBox<K, V, A>,
+    index: usize,
+) -> Option<(K, V)> {
+    let table = map.raw_table_mut();
+    // Safety: the iterator is done with before the table is modified
+    let bucket = unsafe { table.iter() }.nth(index)?;
+    // Safety: the bucket is full, and belongs to `table`
+    Some(unsafe { table.remove(bucket) }.0)
+}
+
+/// Removes some entry of `map` in amortized O(1).
+///
+/// The scan for a full bucket starts at a point that moves along a golden ratio
+/// sequence as the length changes, so it doesn't run into the holes that the previous
+/// pops left. Once the table is mostly empty, it is shrunk first to keep scans short.
+pub(crate) fn pop_any<K: Eq + Hash, V, A: Allocator>(
+    map: &mut SpilledBox<K, V, A>,
+) -> Option<(K, V)> {
+    let len = map.len();
+    if len == 0 {
+        return None;
+    }
+    if len * 8 < map.raw_table().buckets() {
+        // if `K::hash` panics, the map is left as it was
+        map.shrink_to(len);
+    }
+    let table = map.raw_table_mut();
+    let buckets = table.buckets();
+    checked_assert!(buckets.is_power_of_two() && buckets > 1);
+    let shift = u64::BITS - buckets.trailing_zeros();
+    let mut index = ((len as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> shift) as usize;
+    unsafe {
+        // Safety: `index` stays below the bucket count, and some bucket is full
+        while !table.is_bucket_full(index) {
+            index = (index + 1) & (buckets - 1);
+        }
+        // Safety: the bucket is full, and belongs to `table`
+        Some(table.remove(table.bucket(index)).0)
+    }
+}
+
+/// Visits the entries of a spilled map in place, so that each can be updated or removed.
+pub(crate) struct RawCursor<'a, K: Eq + Hash, V, A: Allocator> {
+    table: &'a mut RawTable<(K, V), A>,
+    iter: RawIter<(K, V)>,
+}
+
+impl<'a, K: Eq + Hash, V, A: Allocator> RawCursor<'a, K, V, A> {
+    #[inline]
+    pub(crate) fn new(map: &'a mut SpilledBox<K, V, A>) -> Self {
+        let table = map.raw_table_mut();
+        // Safety: the table outlives the iterator, and is never grown while iterating.
+        // Only buckets that were yielded are removed, which the iterator allows.
+        let iter = unsafe { table.iter() };
+        Self { table, iter }
+    }
+
+    #[inline]
+    pub(crate) fn next(&mut self) -> Option<RawEntry<'_, K, V, A>> {
+        let bucket = self.iter.next()?;
+        Some(RawEntry {
+            table: self.table,
+            bucket,
+        })
+    }
+}
+
+/// The entry a [`RawCursor`] is at, which is always full.
+pub(crate) struct RawEntry<'c, K, V, A: Allocator> {
+    table: &'c mut RawTable<(K, V), A>,
+    bucket: Bucket<(K, V)>,
+}
+
+impl<'c, K, V, A: Allocator> RawEntry<'c, K, V, A> {
+    #[inline]
+    pub(crate) fn get(&self) -> &(K, V) {
+        // Safety: the bucket is full, and the table is borrowed for as long as `self`
+        unsafe { self.bucket.as_ref() }
+    }
+
+    #[inline]
+    pub(crate) fn get_mut(&mut self) -> &mut (K, V) {
+        // Safety: the bucket is full, and the table is borrowed for as long as `self`
+        unsafe { self.bucket.as_mut() }
+    }
+
+    #[inline]
+    pub(crate) fn into_mut(self) -> &'c mut (K, V) {
+        // Safety: the bucket is full, and the table is borrowed for `'c`
+        unsafe { self.bucket.as_mut() }
+    }
+
+    #[inline]
+    pub(crate) fn remove(self) -> (K, V) {
+        // Safety: the bucket is full, and belongs to `table`
+        unsafe { self.table.remove(self.bucket) }.0
+    }
+}
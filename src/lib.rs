@@ -80,6 +80,19 @@
 //! ]);
 //! ```
 //!
+//! Or with the [`compact_map!`] macro, which infers the size:
+//!
+//! ```
+//! use compact_map::compact_map;
+//!
+//! let solar_distance = compact_map! {
+//!     "Mercury" => 0.4,
+//!     "Venus" => 0.7,
+//!     "Earth" => 1.0,
+//!     "Mars" => 1.5,
+//! };
+//! ```
+//!
 //! `CompactMap` implements an [`Entry` API](CompactMap::entry), which allows
 //! for complex methods of getting, setting, updating and removing keys and
 //! their values:
@@ -112,8 +125,56 @@
 //! player_stats.entry("mana").and_modify(|mana| *mana += 200).or_insert(100);
 //! ```
 //!
+//! ## Allocators
+//!
+//! A spilled map is a [`hashbrown::HashMap`], which can live in any [`Allocator`] from
+//! `allocator-api2`, passed to [`CompactMap::new_in`]. The map only touches the allocator
+//! once it spills: the spilled map and its box are both allocated in it. [`SpilledMap`]
+//! names the type of the spilled map.
+//!
+//! ```
+//! use allocator_api2::alloc::AllocError;
+//! use compact_map::{Allocator, CompactMap, Global};
+//! use std::alloc::Layout;
+//! use std::cell::Cell;
+//! use std::ptr::NonNull;
+//!
+//! #[derive(Clone, Copy)]
+//! struct Counting<'a>(&'a Cell<usize>);
+//!
+//! unsafe impl Allocator for Counting<'_> {
+//!     fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+//!         self.0.set(self.0.get() + 1);
+//!         Global.allocate(layout)
+//!     }
+//!
+//!     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+//!         Global.deallocate(ptr, layout)
+//!     }
+//! }
+//!
+//! let allocations = Cell::new(0);
+//! let mut map = CompactMap::<u32, u32, 2, _>::new_in(Counting(&allocations));
+//! map.extend([(1, 1), (2, 2)]);
+//! assert_eq!(allocations.get(), 0);
+//!
+//! map.insert(3, 3);
+//! assert!(map.spilled());
+//! // the table, and the box it lives in
+//! assert_eq!(allocations.get(), 2);
+//!
+//! let map = map.into_hashmap();
+//! assert_eq!(map.len(), 3);
+//! ```
+//!
 //! ## Optional Features
 //!
+//! ### `checked`
+//!
+//! This feature turns the safety preconditions of the crate's internal `unsafe` code into
+//! panicking assertions, at some runtime cost. It is meant for tests and fuzzing, together with
+//! [`CompactMap::assert_invariants`].
+//!
 //! ### `map_entry_replace`
 //!
 //! **This feature is unstable and requires a nightly build of the Rust toolchain.**
@@ -167,6 +228,73 @@
 //! Tracking issue:
 //! - [rust-lang/rust#97601](https://github.com/rust-lang/rust/issues/97601)
 //! - [rust-lang/rust#104642](https://github.com/rust-lang/rust/issues/104642)
+//!
+//! ### `try_reserve_kind`
+//!
+//! **This feature is unstable and requires a nightly build of the Rust toolchain.**
+//!
+//! *This feature enables the `try_reserve_kind` feature gate.*
+//!
+//! This feature fills in the failed allocation's layout in [`TryReserveErrorKind::AllocError`]
+//! for errors converted from std's `TryReserveError`. Errors from the map itself always carry it.
+//!
+//! Tracking issue: [rust-lang/rust#48043](https://github.com/rust-lang/rust/issues/48043)
+//!
+//! ### `equivalent`
+//!
+//! This feature lets lookups take any key type implementing [`Equivalent`], the trait
+//! hashbrown and indexmap use, instead of only borrowed forms of the key type.
+//! It only changes the key comparison, so the map is laid out and spills the same either way.
+//! [`Lookup`] names the accepted key types either way. This applies to [`CompactMap::get`],
+//! [`CompactMap::get_key_value`], [`CompactMap::get_mut`], [`CompactMap::contains_key`],
+//! [`CompactMap::remove`], [`CompactMap::remove_entry`], [`CompactMap::update`] and
+//! [`CompactMap::get_many_mut`].
+//!
+//! ```
+//! # #[cfg(feature = "equivalent")] {
+//! use compact_map::{CompactMap, Equivalent};
+//!
+//! #[derive(Hash)]
+//! struct Query<'a>(&'a str, u32);
+//!
+//! // hashes like `(String, u32)`, since tuples hash their fields in order
+//! impl Equivalent<(String, u32)> for Query<'_> {
+//!     fn equivalent(&self, key: &(String, u32)) -> bool {
+//!         self.0 == key.0 && self.1 == key.1
+//!     }
+//! }
+//!
+//! let mut map: CompactMap<(String, u32), &str, 2> = CompactMap::new();
+//! for i in 0..4 {
+//!     map.insert((format!("v{i}"), i), "value");
+//! }
+//! assert!(map.spilled());
+//! assert!(map.contains_key(&Query("v3", 3)));
+//! assert_eq!(map.remove(&Query("v1", 1)), Some("value"));
+//! assert_eq!(map.get(&Query("v1", 1)), None);
+//! # }
+//! ```
+//!
+//! ### `arbitrary`
+//!
+//! This feature implements [`arbitrary::Arbitrary`](::arbitrary::Arbitrary) for [`CompactMap`],
+//! for use in fuzz targets.
+//!
+//! ```
+//! # #[cfg(feature = "arbitrary")] {
+//! use arbitrary::{Arbitrary, Unstructured};
+//! use compact_map::CompactMap;
+//!
+//! let mut u = Unstructured::new(&[1, 2, 3, 4, 5, 6, 7, 8]);
+//! let map = CompactMap::<u8, u8, 4>::arbitrary(&mut u).unwrap();
+//! assert_eq!(map.len(), map.iter().count());
+//! # }
+//! ```
+//!
+//! ### `proptest`
+//!
+//! This feature adds the [`proptest::compact_map`] strategy, which generates maps on both
+//! sides of the inline capacity.
 
 #![deny(missing_docs)]
 #![allow(clippy::manual_map)]
@@ -177,29 +305,79 @@
 #![cfg_attr(feature = "map_try_insert", feature(map_try_insert))] // issue 82766
 #![cfg_attr(feature = "many_mut", feature(map_many_mut))] // issue 97601
 #![cfg_attr(feature = "many_mut", feature(get_many_mut))] // issue 104642
+#![cfg_attr(feature = "try_reserve_kind", feature(try_reserve_kind))] // issue 48043
 
 use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+#[cfg(doc)]
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::fmt;
 use std::fmt::Debug;
 use std::hash::{BuildHasher, Hash};
 use std::iter::FusedIterator;
 use std::ops::Index;
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
 mod base;
+#[macro_use]
+mod macros;
+mod map_like;
+#[cfg_attr(docsrs, doc(cfg(feature = "proptest")))]
+#[cfg(feature = "proptest")]
+pub mod proptest;
+mod stable;
 mod utils;
 #[cfg(feature = "map_try_insert")]
 pub use base::entry::OccupiedError;
 pub use base::{
+    cursor::{CursorEntry, CursorMut},
     entry::{Entry, OccupiedEntry, VacantEntry},
-    TryReserveError,
+    lookup::Lookup,
+    spilled::{Allocator, Global},
+    DuplicateKeyError, Storage, TryReserveError, TryReserveErrorKind,
 };
+pub use map_like::{MapEntry, MapLike, MapLikeEntry, MapLikeMut};
+pub use stable::{Handle, StableCompactMap, StableIntoIter, StableIter, StableIterMut};
+
+#[cfg_attr(docsrs, doc(cfg(feature = "equivalent")))]
+#[cfg(feature = "equivalent")]
+pub use equivalent::Equivalent;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::utils::{has_duplicate_literals, inline_capacity};
+}
+
+/// The map a [`CompactMap`] spills into.
+///
+/// This is a [`hashbrown::HashMap`] allocated in `A`, which gives the spilled map the
+/// allocator and the in-place removals that std [`HashMap`] lacks.
+pub type SpilledMap<K, V, S = RandomState, A = Global> = base::spilled::Map<K, V, S, A>;
 
 const DEFAULT_MAX_INLINE_ENTRIES: usize = 16;
 
 /// A map that inlines entries to avoid heap allocations for small maps.
-pub struct CompactMap<K, V, const N: usize> {
-    base: base::MapImpl<K, V, N>,
+///
+/// # Size
+///
+/// Once spilled, the entries live in a boxed [`HashMap`], so the size of a `CompactMap` is
+/// driven by its inline storage rather than by the size of a `HashMap`. Whether the map has
+/// spilled is encoded in a niche of the inline length, so there is no separate tag: a
+/// `CompactMap` is the `N` entries plus one `usize`, and never smaller than two pointers.
+///
+/// ```
+/// use compact_map::CompactMap;
+/// use std::collections::HashMap;
+/// use std::mem::size_of;
+///
+/// assert_eq!(size_of::<CompactMap<u8, u8, 4>>(), 16);
+/// assert_eq!(size_of::<CompactMap<u64, u64, 4>>(), 4 * 16 + 8);
+/// assert!(size_of::<CompactMap<u8, u8, 4>>() < size_of::<HashMap<u8, u8>>());
+/// ```
+pub struct CompactMap<K, V, const N: usize, A: Allocator = Global> {
+    base: base::MapImpl<K, V, N, A>,
 }
 
 impl<K, V, const N: usize> CompactMap<K, V, N> {
@@ -220,6 +398,101 @@ impl<K, V, const N: usize> CompactMap<K, V, N> {
             base: base::MapImpl::new(),
         }
     }
+}
+
+impl<K, V, const N: usize> CompactMap<K, V, N>
+where
+    K: Eq + Hash,
+{
+    /// Creates a map from an iterator, failing if it yields the same key twice.
+    ///
+    /// Unlike [`FromIterator`], where later duplicates overwrite earlier ones, the
+    /// [`DuplicateKeyError`] holds the first pair whose key was already yielded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let map = CompactMap::<_, _, 4>::try_from_unique_iter([("a", 1), ("b", 2)]).unwrap();
+    /// assert_eq!(map.len(), 2);
+    ///
+    /// let err = CompactMap::<_, _, 4>::try_from_unique_iter([("a", 1), ("b", 2), ("a", 3)])
+    ///     .unwrap_err();
+    /// assert_eq!(err.into_inner(), ("a", 3));
+    ///
+    /// // also once spilled
+    /// let err = CompactMap::<_, _, 4>::try_from_unique_iter((0..10).chain([7]).map(|i| (i, i)))
+    ///     .unwrap_err();
+    /// assert_eq!(err.key(), &7);
+    /// ```
+    pub fn try_from_unique_iter<T>(iter: T) -> Result<Self, DuplicateKeyError<K, V>>
+    where
+        T: IntoIterator<Item = (K, V)>,
+    {
+        let mut map = Self::new();
+        match map.base.extend_strict(iter) {
+            Ok(()) => Ok(map),
+            Err((k, v)) => Err(DuplicateKeyError::new(k, v)),
+        }
+    }
+
+    /// Creates a map from an iterator, resolving duplicate keys with `f`.
+    ///
+    /// For a key yielded more than once, the value becomes `f(existing, new)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let map: CompactMap<_, _, 4> =
+    ///     CompactMap::from_iter_with([("a", 1), ("b", 2), ("a", 3)], |existing, _| existing);
+    /// assert_eq!(map["a"], 1);
+    ///
+    /// let sums: CompactMap<_, _, 4> =
+    ///     CompactMap::from_iter_with([("a", 1), ("b", 2), ("a", 3)], |existing, new| existing + new);
+    /// assert_eq!(sums["a"], 4);
+    /// ```
+    pub fn from_iter_with<T, F>(iter: T, mut f: F) -> Self
+    where
+        T: IntoIterator<Item = (K, V)>,
+        F: FnMut(V, V) -> V,
+    {
+        let mut map = Self::new();
+        map.base
+            .merge_with(iter, |_, existing, new| f(existing, new));
+        map
+    }
+}
+
+impl<K, V, const N: usize, A: Allocator> CompactMap<K, V, N, A> {
+    /// Creates an empty `CompactMap` whose spilled map is allocated in `alloc`.
+    ///
+    /// Nothing is allocated until the map spills.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::{CompactMap, Global};
+    ///
+    /// let mut map: CompactMap<i32, i32, 2> = CompactMap::new_in(Global);
+    /// map.extend([(1, 1), (2, 2), (3, 3)]);
+    /// assert!(map.spilled());
+    /// ```
+    #[inline(always)]
+    #[must_use]
+    pub const fn new_in(alloc: A) -> Self {
+        Self {
+            base: base::MapImpl::new_in(alloc),
+        }
+    }
+
+    /// Returns a reference to the allocator the spilled map is allocated in.
+    #[inline]
+    pub fn allocator(&self) -> &A {
+        self.base.allocator()
+    }
 
     /// Returns `true` if the data has spilled into an std `HashMap`.
     ///
@@ -239,6 +512,64 @@ impl<K, V, const N: usize> CompactMap<K, V, N> {
         self.base.spilled()
     }
 
+    /// Returns the inline entries as a slice, or `None` if the map has spilled.
+    ///
+    /// The entries are in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 2> = CompactMap::new();
+    /// map.insert(1, 2);
+    /// assert_eq!(map.as_inline_slice(), Some(&[(1, 2)][..]));
+    ///
+    /// map.spill();
+    /// assert_eq!(map.as_inline_slice(), None);
+    /// ```
+    #[inline]
+    pub fn as_inline_slice(&self) -> Option<&[(K, V)]> {
+        self.base.as_inline_slice()
+    }
+
+    /// Returns the spilled [`SpilledMap`], or `None` if the map is heapless.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 2> = CompactMap::new();
+    /// map.insert(1, 2);
+    /// assert!(map.as_hashmap().is_none());
+    ///
+    /// map.spill();
+    /// assert_eq!(map.as_hashmap().unwrap()[&1], 2);
+    /// ```
+    #[inline]
+    pub fn as_hashmap(&self) -> Option<&SpilledMap<K, V, RandomState, A>> {
+        self.base.as_hashmap()
+    }
+
+    /// Consumes the map, returning its underlying storage without moving any entry around.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::{CompactMap, Storage};
+    ///
+    /// let map: CompactMap<i32, i32, 2> = CompactMap::from([(1, 2)]);
+    /// match map.into_inner() {
+    ///     Storage::Inline(vec) => assert_eq!(vec, [(1, 2)]),
+    ///     Storage::Spilled(_) => unreachable!(),
+    /// }
+    /// ```
+    #[inline]
+    pub fn into_inner(self) -> Storage<K, V, N, A> {
+        self.base.into_inner()
+    }
+
     /// Returns the number of elements the map can hold without reallocating.
     ///
     /// When spilled, this number is a lower bound;
@@ -314,7 +645,7 @@ impl<K, V, const N: usize> CompactMap<K, V, N> {
     /// - When heapless: iterating over keys takes O(len) time.
     /// - When spilled: as per in [std docs](HashMap::keys), iterating over keys takes O(capacity) time.
     #[inline]
-    pub fn into_keys(self) -> IntoKeys<K, V, N> {
+    pub fn into_keys(self) -> IntoKeys<K, V, N, A> {
         IntoKeys {
             inner: self.base.into_iter(),
         }
@@ -409,7 +740,7 @@ impl<K, V, const N: usize> CompactMap<K, V, N> {
     /// - When heapless: iterating over keys takes O(len) time.
     /// - When spilled: as per in [std docs](HashMap::keys), iterating over keys takes O(capacity) time.
     #[inline]
-    pub fn into_values(self) -> IntoValues<K, V, N> {
+    pub fn into_values(self) -> IntoValues<K, V, N, A> {
         IntoValues {
             inner: self.base.into_iter(),
         }
@@ -537,7 +868,7 @@ impl<K, V, const N: usize> CompactMap<K, V, N> {
     /// assert!(a.is_empty());
     /// ```
     #[inline]
-    pub fn drain(&mut self) -> Drain<'_, K, V, N> {
+    pub fn drain(&mut self) -> Drain<'_, K, V, N, A> {
         Drain {
             base: self.base.drain(),
         }
@@ -579,7 +910,7 @@ impl<K, V, const N: usize> CompactMap<K, V, N> {
     #[cfg_attr(docsrs, doc(cfg(feature = "extract_if")))]
     #[cfg(feature = "extract_if")]
     #[inline]
-    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F, N>
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F, N, A>
     where
         F: FnMut(&K, &mut V) -> bool,
     {
@@ -634,7 +965,7 @@ impl<K, V, const N: usize> CompactMap<K, V, N> {
     }
 }
 
-impl<K, V, const N: usize> CompactMap<K, V, N>
+impl<K, V, const N: usize, A: Allocator + Clone> CompactMap<K, V, N, A>
 where
     K: Eq + Hash,
 {
@@ -656,6 +987,9 @@ where
     /// ```
     /// use compact_map::CompactMap;
     /// let mut map: CompactMap<&str, i32, 16> = CompactMap::new();
+    /// map.reserve(8);
+    /// assert!(!map.spilled());
+    ///
     /// map.reserve(32);
     /// assert!(map.capacity() >= 32);
     /// assert!(map.spilled());
@@ -678,15 +1012,23 @@ where
     /// # Errors
     ///
     /// If the capacity overflows, or the allocator reports a failure, then an error
-    /// is returned.
+    /// is returned. Its [`kind`](TryReserveError::kind) tells the two apart.
     ///
     /// # Examples
     ///
     /// ```
-    /// use compact_map::CompactMap;
+    /// use compact_map::{CompactMap, TryReserveErrorKind};
     /// let mut map: CompactMap<&str, i32, 16> = CompactMap::new();
     ///
     /// map.try_reserve(10).expect("why is the test harness OOMing on a handful of bytes?");
+    ///
+    /// let err = map.try_reserve(usize::MAX).unwrap_err();
+    /// assert_eq!(err.kind(), TryReserveErrorKind::CapacityOverflow);
+    ///
+    /// let mut map: CompactMap<u64, u64, 16> = CompactMap::new();
+    /// map.spill();
+    /// let err = map.try_reserve(usize::MAX / 2).unwrap_err();
+    /// assert_eq!(err.kind(), TryReserveErrorKind::CapacityOverflow);
     /// ```
     #[inline]
     pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
@@ -726,19 +1068,50 @@ where
     #[inline]
     pub fn shrink_into_heapless<const M: usize>(
         self,
-    ) -> Result<CompactMap<K, V, M>, CompactMap<K, V, N>> {
+    ) -> Result<CompactMap<K, V, M, A>, CompactMap<K, V, N, A>> {
         self.base
             .shrink_into_heapless()
             .map(|base| CompactMap { base })
             .map_err(|base| CompactMap { base })
     }
 
-    /// This is a proxy to the underlying [`HashMap::shrink_to_fit`] method.
-    /// And it's a no-op if the map is heapless.
+    /// Converts the map into a map with inline capacity `M`, for any `N` and `M`.
+    ///
+    /// Inline entries are moved with a plain memory copy when they fit in `M`, and spill into a
+    /// [`HashMap`] when they don't. A spilled map stays spilled; use
+    /// [`shrink_to_fit`](CompactMap::shrink_to_fit) afterwards to move it back inline.
+    ///
+    /// Unlike [`shrink_into_heapless`](CompactMap::shrink_into_heapless), this never fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 2), (3, 4)]);
+    /// let map: CompactMap<i32, i32, 16> = map.into_inline_capacity();
+    /// assert!(!map.spilled());
+    ///
+    /// let map: CompactMap<i32, i32, 1> = map.into_inline_capacity();
+    /// assert!(map.spilled());
+    /// assert_eq!(map[&3], 4);
+    /// ```
+    #[inline]
+    pub fn into_inline_capacity<const M: usize>(self) -> CompactMap<K, V, M, A> {
+        CompactMap {
+            base: self.base.into_inline_capacity(),
+        }
+    }
+
+    /// Shrinks the capacity of the map as much as possible.
+    ///
+    /// If the map has spilled but its entries fit in `N`, they are moved back inline,
+    /// and the capacity becomes `N` again. Otherwise, this is a proxy to the underlying
+    /// [`HashMap::shrink_to_fit`] method: it will drop down as much as possible while
+    /// maintaining the internal rules and possibly leaving some space in accordance
+    /// with the resize policy.
     ///
-    /// Shrinks the capacity of the map as much as possible. It will drop
-    /// down as much as possible while maintaining the internal rules
-    /// and possibly leaving some space in accordance with the resize policy.
+    /// It's a no-op if the map is heapless.
     ///
     /// # Examples
     ///
@@ -751,21 +1124,30 @@ where
     /// map.insert(3, 4);
     /// assert!(map.capacity() >= 100);
     /// map.shrink_to_fit();
-    /// assert!(map.capacity() >= 2);
+    /// assert!(!map.spilled());
+    /// assert_eq!(map.capacity(), 10);
+    ///
+    /// map.extend((5..20).map(|x| (x, x)));
+    /// map.remove(&5);
+    /// map.shrink_to_fit();
+    /// assert!(map.spilled());
+    /// assert!(map.capacity() >= 16);
     /// ```
     #[inline]
     pub fn shrink_to_fit(&mut self) {
         self.base.shrink_to_fit();
     }
 
-    /// This is a proxy to the underlying [`HashMap::shrink_to`] method.
-    /// And it's a no-op if the map is heapless.
+    /// Shrinks the capacity of the map with a lower limit.
     ///
-    /// Shrinks the capacity of the map with a lower limit. It will drop
-    /// down no lower than the supplied limit while maintaining the internal rules
-    /// and possibly leaving some space in accordance with the resize policy.
+    /// If the map has spilled but both its entries and `min_capacity` fit in `N`, the entries
+    /// are moved back inline, and the capacity becomes `N` again. Otherwise, this is a proxy
+    /// to the underlying [`HashMap::shrink_to`] method: it will drop down no lower than the
+    /// supplied limit while maintaining the internal rules and possibly leaving some space in
+    /// accordance with the resize policy.
     ///
-    /// If the current capacity is less than the lower limit, this is a no-op.
+    /// If the current capacity is less than the lower limit, or the map is heapless,
+    /// this is a no-op.
     ///
     /// # Examples
     ///
@@ -777,16 +1159,43 @@ where
     /// map.insert(1, 2);
     /// map.insert(3, 4);
     /// assert!(map.capacity() >= 100);
-    /// map.shrink_to(10);
-    /// assert!(map.capacity() >= 10);
+    /// map.shrink_to(20);
+    /// assert!(map.spilled());
+    /// assert!(map.capacity() >= 20);
     /// map.shrink_to(0);
-    /// assert!(map.capacity() >= 2);
+    /// assert!(!map.spilled());
+    /// assert_eq!(map.capacity(), 10);
     /// ```
     #[inline]
     pub fn shrink_to(&mut self, min_capacity: usize) {
         self.base.shrink_to(min_capacity);
     }
 
+    /// Returns a mutable reference to the value of `key`, inserting the result of the
+    /// fallible `f` first if the key is not in the map.
+    ///
+    /// If `f` fails, its error is returned and the map is left unchanged; in particular,
+    /// it doesn't spill. This is [`Entry::or_try_insert_with`] on the key's entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut cache: CompactMap<&str, u32, 4> = CompactMap::new();
+    ///
+    /// assert_eq!(cache.get_or_try_insert_with("1", || "1".parse()), Ok(&mut 1));
+    /// assert!(cache.get_or_try_insert_with("x", || "x".parse()).is_err());
+    /// assert_eq!(cache.len(), 1);
+    /// ```
+    #[inline]
+    pub fn get_or_try_insert_with<E, F>(&mut self, key: K, f: F) -> Result<&mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        self.entry(key).or_try_insert_with(f)
+    }
+
     /// Gets the given key's corresponding entry in the map for in-place manipulation.
     ///
     /// # Examples
@@ -806,7 +1215,7 @@ where
     /// assert_eq!(letters.get(&'y'), None);
     /// ```
     #[inline]
-    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, N> {
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, N, A> {
         self.base.entry(key)
     }
 
@@ -815,6 +1224,8 @@ where
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
     /// the key type.
+    /// With the `equivalent` feature, it may also be any type `Equivalent` to it,
+    /// see [`Lookup`].
     ///
     /// # Examples
     ///
@@ -829,8 +1240,7 @@ where
     #[inline]
     pub fn get<Q>(&self, k: &Q) -> Option<&V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.base.get(k)
     }
@@ -840,6 +1250,8 @@ where
     /// The supplied key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
     /// the key type.
+    /// With the `equivalent` feature, it may also be any type `Equivalent` to it,
+    /// see [`Lookup`].
     ///
     /// # Examples
     ///
@@ -854,8 +1266,7 @@ where
     #[inline]
     pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.base.get_key_value(k)
     }
@@ -902,14 +1313,23 @@ where
     ///     "Athenæum",
     /// ]);
     /// assert_eq!(got, None);
+    ///
+    /// // The same goes for a spilled map
+    /// let mut spilled: CompactMap<u32, u32, 2> = (0..8).map(|i| (i, i * 10)).collect();
+    /// assert!(spilled.spilled());
+    /// let [a, b] = spilled.get_many_mut([&1, &5]).unwrap();
+    /// std::mem::swap(a, b);
+    /// assert_eq!(spilled[&1], 50);
+    /// assert_eq!(spilled[&5], 10);
+    /// assert_eq!(spilled.get_many_mut([&1, &8]), None);
+    /// assert_eq!(spilled.get_many_mut([&1, &1]), None);
     /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "many_mut")))]
     #[cfg(feature = "many_mut")]
     #[inline]
     pub fn get_many_mut<Q, const M: usize>(&mut self, ks: [&Q; M]) -> Option<[&'_ mut V; M]>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.base.get_many_mut(ks)
     }
@@ -978,6 +1398,8 @@ where
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
     /// the key type.
+    /// With the `equivalent` feature, it may also be any type `Equivalent` to it,
+    /// see [`Lookup`].
     ///
     /// # Examples
     ///
@@ -992,8 +1414,7 @@ where
     #[inline]
     pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.base.contains_key(k)
     }
@@ -1003,6 +1424,8 @@ where
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
     /// the key type.
+    /// With the `equivalent` feature, it may also be any type `Equivalent` to it,
+    /// see [`Lookup`].
     ///
     /// # Examples
     ///
@@ -1019,8 +1442,7 @@ where
     #[inline]
     pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.base.get_mut(k)
     }
@@ -1052,6 +1474,493 @@ where
         self.base.insert(k, v)
     }
 
+    /// Inserts a key-value pair into the map without checking whether the key is already present,
+    /// and returns a mutable reference to the value.
+    ///
+    /// When heapless, this skips the linear scan for an equivalent key. When spilled, it skips
+    /// the probe for one, and only looks for an empty slot.
+    ///
+    /// # Safety
+    ///
+    /// The key must not be in the map already. Otherwise the map would hold the key twice,
+    /// and the behavior of any later operation on the map is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, &str, 16> = CompactMap::new();
+    /// // Safety: the map is empty
+    /// *unsafe { map.insert_unique_unchecked(1, "a") } = "b";
+    /// assert_eq!(map[&1], "b");
+    /// ```
+    #[inline]
+    pub unsafe fn insert_unique_unchecked(&mut self, k: K, v: V) -> &mut V {
+        self.base.insert_unique_unchecked(k, v)
+    }
+
+    /// Extends the map with key-value pairs whose keys are known to be unique.
+    ///
+    /// Like [`Extend::extend`], the map spills at most once, straight to a [`HashMap`] sized
+    /// after the iterator's lower bound when it doesn't fit inline. When heapless, this also
+    /// skips the linear scan for an equivalent key on every insert.
+    ///
+    /// # Safety
+    ///
+    /// No key may be in the map already, nor be yielded twice by the iterator. Otherwise the map
+    /// would hold the key twice, and the behavior of any later operation on the map is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 16> = CompactMap::new();
+    /// // Safety: the keys are distinct and the map is empty
+    /// unsafe { map.extend_unique((0..8).map(|x| (x, x * 10))) };
+    /// assert_eq!(map.len(), 8);
+    /// assert!(!map.spilled());
+    ///
+    /// // Safety: the keys are distinct and not in the map
+    /// unsafe { map.extend_unique((8..100).map(|x| (x, x * 10))) };
+    /// assert_eq!(map.len(), 100);
+    /// assert_eq!(map[&42], 420);
+    /// ```
+    #[inline]
+    pub unsafe fn extend_unique<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.base.extend_unique(iter)
+    }
+
+    /// Moves all entries from `other` into `self`, leaving `other` empty.
+    ///
+    /// If a key from `other` is already present in `self`, the respective value from `self`
+    /// will be overwritten with the respective value from `other`.
+    ///
+    /// # Performance
+    ///
+    /// - When `other` has spilled and is at least as large as `self`, its allocation is reused.
+    /// - When both are heapless and the merged entries fit in `N`, `self` doesn't spill, and
+    ///   new entries are moved with a single memory copy.
+    /// - When `other` is empty, nothing happens, even if it has spilled.
+    /// - Otherwise, `self` spills at most once, sized for all the entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut a: CompactMap<i32, &str, 4> = CompactMap::from([(1, "a"), (2, "b")]);
+    /// let mut b: CompactMap<i32, &str, 8> = CompactMap::from([(2, "c"), (3, "d")]);
+    /// a.append(&mut b);
+    ///
+    /// assert!(b.is_empty());
+    /// assert_eq!(a.len(), 3);
+    /// assert_eq!(a[&1], "a");
+    /// assert_eq!(a[&2], "c");
+    /// assert_eq!(a[&3], "d");
+    ///
+    /// // overlapping keys don't count twice
+    /// let mut c: CompactMap<i32, &str, 4> = CompactMap::from([(0, "e"), (1, "f"), (3, "g")]);
+    /// a.append(&mut c);
+    /// assert!(!a.spilled());
+    /// assert_eq!(a.len(), 4);
+    /// assert_eq!(a[&3], "g");
+    ///
+    /// let mut empty: CompactMap<i32, &str, 2> = (0..10).map(|x| (x, "empty")).collect();
+    /// empty.clear();
+    /// assert!(empty.spilled());
+    /// a.append(&mut empty);
+    /// assert!(!a.spilled());
+    ///
+    /// let mut big: CompactMap<i32, &str, 2> = (0..100).map(|x| (x, "big")).collect();
+    /// a.append(&mut big);
+    /// assert!(big.is_empty());
+    /// assert_eq!(a.len(), 100);
+    /// assert_eq!(a[&1], "big");
+    /// ```
+    #[inline]
+    pub fn append<const M: usize>(&mut self, other: &mut CompactMap<K, V, M, A>) {
+        self.base.append(&mut other.base)
+    }
+
+    /// Merges key-value pairs into the map, resolving conflicts with `f`.
+    ///
+    /// Keys not in the map are inserted. For keys already in the map, the value becomes
+    /// `f(&key, existing, new)`. Like [`Extend::extend`], the map spills at most once.
+    ///
+    /// If `f` panics, the entry for that key is removed from the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut totals: CompactMap<&str, u32, 4> = CompactMap::from([("a", 1), ("b", 2)]);
+    /// totals.merge_with([("b", 10), ("c", 20)], |_, old, new| old + new);
+    ///
+    /// assert_eq!(totals.len(), 3);
+    /// assert_eq!(totals["a"], 1);
+    /// assert_eq!(totals["b"], 12);
+    /// assert_eq!(totals["c"], 20);
+    /// ```
+    #[inline]
+    pub fn merge_with<T, F>(&mut self, other: T, f: F)
+    where
+        T: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, V, V) -> V,
+    {
+        self.base.merge_with(other, f)
+    }
+
+    /// Retains only the keys also present in `other`, updating their values with `f`.
+    ///
+    /// `f` is called with each retained key, its value in this map and its value in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut prices: CompactMap<&str, u32, 4> = CompactMap::from([("a", 10), ("b", 20)]);
+    /// let discounts: CompactMap<&str, u32, 8> = CompactMap::from([("b", 5), ("c", 1)]);
+    /// prices.intersection_with(&discounts, |_, price, discount| *price -= discount);
+    ///
+    /// assert_eq!(prices.len(), 1);
+    /// assert_eq!(prices["b"], 15);
+    /// ```
+    #[inline]
+    pub fn intersection_with<W, F, const M: usize, B>(
+        &mut self,
+        other: &CompactMap<K, W, M, B>,
+        mut f: F,
+    ) where
+        F: FnMut(&K, &mut V, &W),
+        B: Allocator + Clone,
+    {
+        self.retain(|k, v| match other.get(k) {
+            Some(w) => {
+                f(k, v, w);
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Removes every key present in `other` from the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 1), (2, 2), (3, 3)]);
+    /// let other: CompactMap<i32, (), 2> = CompactMap::from([(2, ()), (4, ())]);
+    /// map.difference(&other);
+    ///
+    /// let mut keys: Vec<_> = map.keys().copied().collect();
+    /// keys.sort_unstable();
+    /// assert_eq!(keys, [1, 3]);
+    /// ```
+    #[inline]
+    pub fn difference<W, const M: usize, B: Allocator + Clone>(
+        &mut self,
+        other: &CompactMap<K, W, M, B>,
+    ) {
+        if self.spilled() && other.len() < self.len() {
+            for k in other.keys() {
+                self.remove(k);
+            }
+        } else {
+            self.retain(|k, _| !other.contains_key(k));
+        }
+    }
+
+    /// Retains only the keys also present in `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 1), (2, 2), (3, 3)]);
+    /// let other: CompactMap<i32, (), 2> = CompactMap::from([(2, ()), (4, ())]);
+    /// map.retain_keys_in(&other);
+    ///
+    /// assert_eq!(map.len(), 1);
+    /// assert_eq!(map[&2], 2);
+    /// ```
+    #[inline]
+    pub fn retain_keys_in<W, const M: usize, B: Allocator + Clone>(
+        &mut self,
+        other: &CompactMap<K, W, M, B>,
+    ) {
+        self.retain(|k, _| other.contains_key(k));
+    }
+
+    /// Returns the key-value pair at position `index`, if any.
+    ///
+    /// Positions follow the iteration order, and are only stable between mutations of the map.
+    /// This is O(1) when heapless, but walks the map when spilled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 10), (2, 20)]);
+    /// let (k, v) = map.get_index(1).unwrap();
+    /// assert_eq!(map[k], *v);
+    /// assert_eq!(map.get_index(2), None);
+    /// ```
+    #[inline]
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.base.get_index(index)
+    }
+
+    /// Returns the key and a mutable reference to the value at position `index`, if any.
+    ///
+    /// Positions follow the iteration order, and are only stable between mutations of the map.
+    /// This is O(1) when heapless, but walks the map when spilled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 10), (2, 20)]);
+    /// for index in 0..map.len() {
+    ///     *map.get_index_mut(index).unwrap().1 += 1;
+    /// }
+    /// assert_eq!(map[&1], 11);
+    /// assert_eq!(map[&2], 21);
+    /// ```
+    #[inline]
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.base.get_index_mut(index)
+    }
+
+    /// Removes and returns some entry of the map, or `None` if it is empty.
+    ///
+    /// This is O(1) when heapless, and amortized O(1) when spilled: the entry is taken out
+    /// of the table in place, without hashing. To keep that true while a map is drained,
+    /// a spilled map that has become mostly empty is shrunk first, which does rehash the
+    /// entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut queue: CompactMap<i32, &str, 4> = CompactMap::from([(1, "a"), (2, "b")]);
+    /// let mut done = Vec::new();
+    /// while let Some((k, _)) = queue.pop_any() {
+    ///     done.push(k);
+    /// }
+    /// done.sort_unstable();
+    /// assert_eq!(done, [1, 2]);
+    ///
+    /// let mut spilled: CompactMap<i32, i32, 4> = (0..10).map(|i| (i, i)).collect();
+    /// let mut count = 0;
+    /// while spilled.pop_any().is_some() {
+    ///     count += 1;
+    /// }
+    /// assert_eq!(count, 10);
+    /// ```
+    #[inline]
+    pub fn pop_any(&mut self) -> Option<(K, V)> {
+        self.base.pop_any()
+    }
+
+    /// Removes the entry at position `index` and returns it, if any.
+    ///
+    /// When heapless, the last entry takes the place of the removed one, like
+    /// [`Vec::swap_remove`]. Positions follow the iteration order, and are only stable
+    /// between mutations of the map.
+    ///
+    /// When spilled, this walks the table up to the entry, in O(n), and takes it out in
+    /// place without hashing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 10), (2, 20), (3, 30)]);
+    /// let (k, v) = map.swap_remove_index(0).unwrap();
+    /// assert_eq!(v, k * 10);
+    /// assert!(!map.contains_key(&k));
+    /// assert_eq!(map.len(), 2);
+    /// assert_eq!(map.swap_remove_index(2), None);
+    /// ```
+    #[inline]
+    pub fn swap_remove_index(&mut self, index: usize) -> Option<(K, V)> {
+        self.base.swap_remove_index(index)
+    }
+
+    /// Returns a cursor to visit every entry in arbitrary order, and update or remove it.
+    ///
+    /// Unlike [`retain`](Self::retain), removed entries are handed back, and unlike
+    /// [`extract_if`](Self::extract_if), the kept entries can be updated.
+    /// Each entry is visited exactly once, even though removals reorder a heapless map.
+    ///
+    /// A spilled map is visited in place, so nothing is rehashed, and leaking the cursor
+    /// leaves the map as it was when the cursor stopped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 10), (2, 20), (3, 30)]);
+    /// let mut removed = Vec::new();
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// while let Some(mut entry) = cursor.next() {
+    ///     if entry.key() % 2 == 1 {
+    ///         removed.push(entry.remove_entry());
+    ///     } else {
+    ///         *entry.get_mut() += 1;
+    ///     }
+    /// }
+    /// drop(cursor);
+    ///
+    /// removed.sort_unstable();
+    /// assert_eq!(removed, [(1, 10), (3, 30)]);
+    /// assert_eq!(map.len(), 1);
+    /// assert_eq!(map[&2], 21);
+    ///
+    /// // spilled, and stopping halfway
+    /// let mut map: CompactMap<i32, i32, 4> = (0..10).map(|i| (i, i)).collect();
+    /// let mut cursor = map.cursor_mut();
+    /// for _ in 0..5 {
+    ///     cursor.next().unwrap().remove();
+    /// }
+    /// drop(cursor);
+    /// assert_eq!(map.len(), 5);
+    /// ```
+    #[inline]
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V, N, A> {
+        CursorMut::new(&mut self.base)
+    }
+
+    /// Splits the map in two: the entries for which `pred` returns `true`, and the rest.
+    ///
+    /// Entries are moved as they are, without checking keys again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let map: CompactMap<i32, i32, 4> = (0..8).map(|i| (i, i * 10)).collect();
+    /// let (even, odd) = map.partition(|k, _| k % 2 == 0);
+    ///
+    /// assert_eq!(even.len(), 4);
+    /// assert!(!even.spilled());
+    /// assert_eq!(odd[&3], 30);
+    /// ```
+    #[inline]
+    pub fn partition<F>(self, pred: F) -> (Self, Self)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let (left, right) = self.base.partition(pred);
+        (Self { base: left }, Self { base: right })
+    }
+
+    /// Moves the entries for which `pred` returns `true` into a new map.
+    ///
+    /// Entries are moved as they are, without checking keys again.
+    /// The map keeps its storage, like after [`retain`](Self::retain).
+    ///
+    /// If `pred` panics, the entries moved so far are moved back, so the map loses none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = CompactMap::from([(1, 1), (2, 2), (3, 3)]);
+    /// let even: CompactMap<i32, i32, 2> = map.split_off_if(|k, _| k % 2 == 0);
+    ///
+    /// assert_eq!(map.len(), 2);
+    /// assert_eq!(even.len(), 1);
+    /// assert_eq!(even[&2], 2);
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = (0..10).map(|i| (i, i)).collect();
+    /// let small: CompactMap<i32, i32, 4> = map.split_off_if(|k, _| *k < 3);
+    ///
+    /// assert!(map.spilled());
+    /// assert_eq!(map.len(), 7);
+    /// assert!(!small.spilled());
+    /// assert_eq!(small.len(), 3);
+    /// ```
+    #[inline]
+    pub fn split_off_if<F, const M: usize>(&mut self, pred: F) -> CompactMap<K, V, M, A>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        CompactMap {
+            base: self.base.split_off_if(pred),
+        }
+    }
+
+    /// Transforms every value of the map with `f`, keeping the keys.
+    ///
+    /// A heapless map is converted entry by entry, without checking keys again.
+    /// A spilled map stays spilled, with the same capacity and hasher. Its keys are
+    /// rehashed into the new table, as it doesn't store their hashes, but never compared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let map: CompactMap<&str, i32, 4> = CompactMap::from([("a", 1), ("b", 2)]);
+    /// let map: CompactMap<&str, String, 4> = map.map_values(|k, v| format!("{k}{v}"));
+    ///
+    /// assert_eq!(map["a"], "a1");
+    /// assert_eq!(map["b"], "b2");
+    /// ```
+    #[inline]
+    pub fn map_values<W, F>(self, mut f: F) -> CompactMap<K, W, N, A>
+    where
+        F: FnMut(&K, V) -> W,
+    {
+        match self.try_map_values(|k, v| Ok::<_, Infallible>(f(k, v))) {
+            Ok(map) => map,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Transforms every value of the map with the fallible `f`, keeping the keys.
+    ///
+    /// Stops at the first error, dropping the map.
+    /// Otherwise behaves like [`map_values`](Self::map_values).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let map: CompactMap<&str, &str, 4> = CompactMap::from([("a", "1"), ("b", "2")]);
+    /// let parsed = map.try_map_values(|_, v| v.parse::<i32>()).unwrap();
+    /// assert_eq!(parsed["b"], 2);
+    ///
+    /// let map: CompactMap<&str, &str, 4> = CompactMap::from([("a", "1"), ("b", "x")]);
+    /// assert!(map.try_map_values(|_, v| v.parse::<i32>()).is_err());
+    /// ```
+    #[inline]
+    pub fn try_map_values<W, E, F>(self, f: F) -> Result<CompactMap<K, W, N, A>, E>
+    where
+        F: FnMut(&K, V) -> Result<W, E>,
+    {
+        Ok(CompactMap {
+            base: self.base.try_map_values(f)?,
+        })
+    }
+
     /// Tries to insert a key-value pair into the map, and returns
     /// a mutable reference to the value in the entry.
     ///
@@ -1075,19 +1984,90 @@ where
     /// ```
     #[cfg_attr(docsrs, doc(cfg(feature = "map_try_insert")))]
     #[cfg(feature = "map_try_insert")]
-    pub fn try_insert(&mut self, key: K, value: V) -> Result<&mut V, OccupiedError<'_, K, V, N>> {
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<&mut V, OccupiedError<'_, K, V, N, A>> {
         match self.entry(key) {
             Entry::Occupied(entry) => Err(OccupiedError { entry, value }),
             Entry::Vacant(entry) => Ok(entry.insert(value)),
         }
     }
 
+    /// Inserts, updates or removes the value of a key, depending on the result of `f`.
+    ///
+    /// `f` is given the current value of the key, if any. If it returns `Some`, the key is
+    /// set to that value; if it returns `None`, the key is removed from the map.
+    /// When heapless, the whole operation takes a single scan of the entries.
+    ///
+    /// If `f` panics, the key is removed from the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<&str, u32, 4> = CompactMap::new();
+    /// map.alter("a", |v| Some(v.unwrap_or(0) + 1));
+    /// map.alter("a", |v| Some(v.unwrap_or(0) + 1));
+    /// assert_eq!(map["a"], 2);
+    ///
+    /// map.alter("a", |_| None);
+    /// assert!(map.is_empty());
+    /// ```
+    #[inline]
+    pub fn alter<F>(&mut self, key: K, f: F)
+    where
+        F: FnOnce(Option<V>) -> Option<V>,
+    {
+        self.base.alter(key, f)
+    }
+
+    /// Updates the value of a key in place, and removes the key if `f` returns `false`.
+    ///
+    /// Does nothing if the key is not in the map.
+    /// When heapless, the whole operation takes a single scan of the entries.
+    ///
+    /// The key may be any borrowed form of the map's key type, but
+    /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
+    /// the key type.
+    /// With the `equivalent` feature, it may also be any type `Equivalent` to it,
+    /// see [`Lookup`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut refs: CompactMap<&str, u32, 4> = CompactMap::from([("a", 2)]);
+    /// let release = |count: &mut u32| {
+    ///     *count -= 1;
+    ///     *count > 0
+    /// };
+    ///
+    /// refs.update("a", release);
+    /// assert_eq!(refs["a"], 1);
+    /// refs.update("a", release);
+    /// assert!(!refs.contains_key("a"));
+    /// ```
+    #[inline]
+    pub fn update<Q, F>(&mut self, k: &Q, f: F)
+    where
+        Q: Lookup<K> + ?Sized,
+        F: FnOnce(&mut V) -> bool,
+    {
+        self.base.update(k, f)
+    }
+
     /// Removes a key from the map, returning the value at the key if the key
     /// was previously in the map.
     ///
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
     /// the key type.
+    /// With the `equivalent` feature, it may also be any type `Equivalent` to it,
+    /// see [`Lookup`].
     ///
     /// # Examples
     ///
@@ -1102,8 +2082,7 @@ where
     #[inline]
     pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.base.remove(k)
     }
@@ -1114,6 +2093,8 @@ where
     /// The key may be any borrowed form of the map's key type, but
     /// [`Hash`] and [`Eq`] on the borrowed form *must* match those for
     /// the key type.
+    /// With the `equivalent` feature, it may also be any type `Equivalent` to it,
+    /// see [`Lookup`].
     ///
     /// # Examples
     ///
@@ -1130,37 +2111,62 @@ where
     #[inline]
     pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
+        Q: Lookup<K> + ?Sized,
     {
         self.base.remove_entry(k)
     }
 
-    /// Converts the map into a [`HashMap`].
+    /// Checks the internal invariants of the map, such as key uniqueness in inline storage.
+    ///
+    /// Meant for tests and fuzzing; inline maps are checked in O(len²) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an invariant doesn't hold, which is always a bug in this crate.
     ///
-    /// If the map has spilled into a `HashMap`, this will return that `HashMap`.
-    /// Otherwise, it will create a new `HashMap` and move all the entries into it.
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = (0..4).map(|x| (x, x)).collect();
+    /// map.insert(0, 1);
+    /// map.assert_invariants();
+    /// ```
     #[inline]
-    pub fn into_hashmap(self) -> HashMap<K, V> {
+    pub fn assert_invariants(&self) {
+        self.base.assert_invariants()
+    }
+
+    /// Converts the map into a [`SpilledMap`].
+    ///
+    /// If the map has spilled, this will return the spilled map.
+    /// Otherwise, it will create a new one and move all the entries into it.
+    #[inline]
+    pub fn into_hashmap(self) -> SpilledMap<K, V, RandomState, A> {
         self.base.into_hashmap()
     }
 
-    /// Converts the map into a [`HashMap`] with a given hasher.
+    /// Converts the map into a [`SpilledMap`] with a given hasher.
     ///
-    /// This will always create a new `HashMap` and move all the entries into it.
+    /// This will always create a new map and move all the entries into it.
     ///
     /// See also [`HashMap::with_hasher`].
     #[inline]
-    pub fn into_hashmap_with_hasher<S: BuildHasher>(self, hash_builder: S) -> HashMap<K, V, S> {
-        let mut map = HashMap::with_capacity_and_hasher(self.len(), hash_builder);
+    pub fn into_hashmap_with_hasher<S: BuildHasher>(
+        self,
+        hash_builder: S,
+    ) -> SpilledMap<K, V, S, A> {
+        let alloc = self.allocator().clone();
+        let mut map = base::spilled::with_capacity_and_hasher_in(self.len(), hash_builder, alloc);
         map.extend(self.base);
         map
     }
 
-    /// Converts the map into a [`HashMap`] with at least the specified capacity, using
+    /// Converts the map into a [`SpilledMap`] with at least the specified capacity, using
     /// `hasher` to hash the keys. The capacity will always be at least `self.len()`.
     ///
-    /// This will always create a new `HashMap` and move all the entries into it.
+    /// This will always create a new map and move all the entries into it.
     ///
     /// See also [`HashMap::with_capacity_and_hasher`].
     #[inline]
@@ -1168,19 +2174,24 @@ where
         self,
         capacity: usize,
         hash_builder: S,
-    ) -> HashMap<K, V, S> {
-        let mut map = HashMap::with_capacity_and_hasher(capacity.max(self.len()), hash_builder);
+    ) -> SpilledMap<K, V, S, A> {
+        let capacity = capacity.max(self.len());
+        let alloc = self.allocator().clone();
+        let mut map = base::spilled::with_capacity_and_hasher_in(capacity, hash_builder, alloc);
         map.extend(self.base);
         map
     }
 }
 
-impl<K, V, const N: usize, const M: usize> PartialEq<CompactMap<K, V, M>> for CompactMap<K, V, N>
+impl<K, V, const N: usize, const M: usize, A, B> PartialEq<CompactMap<K, V, M, B>>
+    for CompactMap<K, V, N, A>
 where
     K: Eq + Hash,
     V: PartialEq,
+    A: Allocator,
+    B: Allocator + Clone,
 {
-    fn eq(&self, other: &CompactMap<K, V, M>) -> bool {
+    fn eq(&self, other: &CompactMap<K, V, M, B>) -> bool {
         if self.len() != other.len() {
             return false;
         }
@@ -1190,14 +2201,15 @@ where
     }
 }
 
-impl<K, V, const N: usize> Eq for CompactMap<K, V, N>
+impl<K, V, const N: usize, A> Eq for CompactMap<K, V, N, A>
 where
     K: Eq + Hash,
     V: Eq,
+    A: Allocator + Clone,
 {
 }
 
-impl<K, V, const N: usize> Debug for CompactMap<K, V, N>
+impl<K, V, const N: usize, A: Allocator> Debug for CompactMap<K, V, N, A>
 where
     K: Debug,
     V: Debug,
@@ -1213,10 +2225,11 @@ impl<K, V> Default for CompactMap<K, V, DEFAULT_MAX_INLINE_ENTRIES> {
     }
 }
 
-impl<K, Q: ?Sized, V, const N: usize> Index<&Q> for CompactMap<K, V, N>
+impl<K, Q: ?Sized, V, const N: usize, A> Index<&Q> for CompactMap<K, V, N, A>
 where
     K: Eq + Hash + Borrow<Q>,
     Q: Eq + Hash,
+    A: Allocator + Clone,
 {
     type Output = V;
 
@@ -1243,6 +2256,11 @@ where
     /// let map1: CompactMap<i32, i32, 16> = CompactMap::from([(1, 2), (3, 4)]);
     /// let map2: CompactMap<i32, i32, 32> = [(1, 2), (3, 4)].into();
     /// assert_eq!(map1, map2);
+    ///
+    /// // Later duplicates overwrite earlier ones.
+    /// let map3: CompactMap<i32, i32, 16> = CompactMap::from([(1, 2), (1, 3)]);
+    /// assert_eq!(map3.len(), 1);
+    /// assert_eq!(map3[&1], 3);
     /// ```
     fn from(arr: [(K, V); N]) -> Self {
         Self {
@@ -1335,11 +2353,11 @@ impl<K, V, const N: usize> FusedIterator for Keys<'_, K, V, N> {}
 /// ]);
 /// let iter_keys = map.into_keys();
 /// ```
-pub struct IntoKeys<K, V, const N: usize> {
-    pub(crate) inner: base::iter::IntoIterInner<K, V, N>,
+pub struct IntoKeys<K, V, const N: usize, A: Allocator = Global> {
+    pub(crate) inner: base::iter::IntoIterInner<K, V, N, A>,
 }
 
-impl<K, V, const N: usize> Iterator for IntoKeys<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> Iterator for IntoKeys<K, V, N, A> {
     type Item = K;
 
     #[inline]
@@ -1363,13 +2381,13 @@ impl<K, V, const N: usize> Iterator for IntoKeys<K, V, N> {
         self.inner.fold(init, |acc, (k, _)| f(acc, k))
     }
 }
-impl<K, V, const N: usize> ExactSizeIterator for IntoKeys<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> ExactSizeIterator for IntoKeys<K, V, N, A> {
     #[inline]
     fn len(&self) -> usize {
         self.inner.len()
     }
 }
-impl<K, V, const N: usize> FusedIterator for IntoKeys<K, V, N> {}
+impl<K, V, const N: usize, A: Allocator> FusedIterator for IntoKeys<K, V, N, A> {}
 
 /// An iterator over the values of a `CompactMap`.
 ///
@@ -1494,11 +2512,11 @@ impl<K, V, const N: usize> FusedIterator for ValuesMut<'_, K, V, N> {}
 /// ]);
 /// let iter_keys = map.into_values();
 /// ```
-pub struct IntoValues<K, V, const N: usize> {
-    inner: base::iter::IntoIterInner<K, V, N>,
+pub struct IntoValues<K, V, const N: usize, A: Allocator = Global> {
+    inner: base::iter::IntoIterInner<K, V, N, A>,
 }
 
-impl<K, V, const N: usize> Iterator for IntoValues<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> Iterator for IntoValues<K, V, N, A> {
     type Item = V;
 
     #[inline]
@@ -1522,13 +2540,13 @@ impl<K, V, const N: usize> Iterator for IntoValues<K, V, N> {
         self.inner.fold(init, |acc, (_, v)| f(acc, v))
     }
 }
-impl<K, V, const N: usize> ExactSizeIterator for IntoValues<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> ExactSizeIterator for IntoValues<K, V, N, A> {
     #[inline]
     fn len(&self) -> usize {
         self.inner.len()
     }
 }
-impl<K, V, const N: usize> FusedIterator for IntoValues<K, V, N> {}
+impl<K, V, const N: usize, A: Allocator> FusedIterator for IntoValues<K, V, N, A> {}
 
 /// An iterator over the entries of a `CompactMap`.
 ///
@@ -1653,17 +2671,17 @@ impl<K, V, const N: usize> FusedIterator for IterMut<'_, K, V, N> {}
 /// ]);
 /// let iter = map.into_iter();
 /// ```
-pub struct IntoIter<K, V, const N: usize> {
-    base: base::iter::IntoIterInner<K, V, N>,
+pub struct IntoIter<K, V, const N: usize, A: Allocator = Global> {
+    base: base::iter::IntoIterInner<K, V, N, A>,
 }
 
-impl<K: Debug, V: Debug, const N: usize> Debug for IntoIter<K, V, N> {
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for IntoIter<K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.base.fmt(f)
     }
 }
 
-impl<K, V, const N: usize> Iterator for IntoIter<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> Iterator for IntoIter<K, V, N, A> {
     type Item = (K, V);
 
     #[inline]
@@ -1687,15 +2705,15 @@ impl<K, V, const N: usize> Iterator for IntoIter<K, V, N> {
         self.base.fold(init, f)
     }
 }
-impl<K, V, const N: usize> ExactSizeIterator for IntoIter<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> ExactSizeIterator for IntoIter<K, V, N, A> {
     #[inline]
     fn len(&self) -> usize {
         self.base.len()
     }
 }
-impl<K, V, const N: usize> FusedIterator for IntoIter<K, V, N> {}
+impl<K, V, const N: usize, A: Allocator> FusedIterator for IntoIter<K, V, N, A> {}
 
-impl<'a, K, V, const N: usize> IntoIterator for &'a CompactMap<K, V, N> {
+impl<'a, K, V, const N: usize, A: Allocator> IntoIterator for &'a CompactMap<K, V, N, A> {
     type Item = (&'a K, &'a V);
     type IntoIter = Iter<'a, K, V, N>;
 
@@ -1705,7 +2723,7 @@ impl<'a, K, V, const N: usize> IntoIterator for &'a CompactMap<K, V, N> {
     }
 }
 
-impl<'a, K, V, const N: usize> IntoIterator for &'a mut CompactMap<K, V, N> {
+impl<'a, K, V, const N: usize, A: Allocator> IntoIterator for &'a mut CompactMap<K, V, N, A> {
     type Item = (&'a K, &'a mut V);
     type IntoIter = IterMut<'a, K, V, N>;
 
@@ -1734,17 +2752,17 @@ impl<'a, K, V, const N: usize> IntoIterator for &'a mut CompactMap<K, V, N> {
 /// ]);
 /// let iter = map.drain();
 /// ```
-pub struct Drain<'a, K: 'a, V: 'a, const N: usize> {
-    base: base::drain::DrainInner<'a, K, V, N>,
+pub struct Drain<'a, K: 'a, V: 'a, const N: usize, A: Allocator = Global> {
+    base: base::drain::DrainInner<'a, K, V, N, A>,
 }
 
-impl<K: Debug, V: Debug, const N: usize> Debug for Drain<'_, K, V, N> {
+impl<K: Debug, V: Debug, const N: usize, A: Allocator> Debug for Drain<'_, K, V, N, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.base.fmt(f)
     }
 }
 
-impl<'a, K, V, const N: usize> Iterator for Drain<'a, K, V, N> {
+impl<'a, K, V, const N: usize, A: Allocator> Iterator for Drain<'a, K, V, N, A> {
     type Item = (K, V);
 
     #[inline]
@@ -1768,13 +2786,13 @@ impl<'a, K, V, const N: usize> Iterator for Drain<'a, K, V, N> {
         self.base.fold(init, f)
     }
 }
-impl<K, V, const N: usize> ExactSizeIterator for Drain<'_, K, V, N> {
+impl<K, V, const N: usize, A: Allocator> ExactSizeIterator for Drain<'_, K, V, N, A> {
     #[inline]
     fn len(&self) -> usize {
         self.base.len()
     }
 }
-impl<K, V, const N: usize> FusedIterator for Drain<'_, K, V, N> {}
+impl<K, V, const N: usize, A: Allocator> FusedIterator for Drain<'_, K, V, N, A> {}
 
 /// A draining, filtering iterator over the entries of a `CompactMap`.
 ///
@@ -1797,16 +2815,16 @@ impl<K, V, const N: usize> FusedIterator for Drain<'_, K, V, N> {}
 #[cfg_attr(docsrs, doc(cfg(feature = "extract_if")))]
 #[cfg(feature = "extract_if")]
 #[must_use = "iterators are lazy and do nothing unless consumed"]
-pub struct ExtractIf<'a, K, V, F, const N: usize>
+pub struct ExtractIf<'a, K, V, F, const N: usize, A: Allocator = Global>
 where
     F: FnMut(&K, &mut V) -> bool,
 {
-    base: base::extract_if::ExtractIfInner<'a, K, V, F, N>,
+    base: base::extract_if::ExtractIfInner<'a, K, V, F, N, A>,
 }
 
 #[cfg_attr(docsrs, doc(cfg(feature = "extract_if")))]
 #[cfg(feature = "extract_if")]
-impl<K, V, F, const N: usize> Iterator for ExtractIf<'_, K, V, F, N>
+impl<K, V, F, const N: usize, A: Allocator> Iterator for ExtractIf<'_, K, V, F, N, A>
 where
     F: FnMut(&K, &mut V) -> bool,
 {
@@ -1824,14 +2842,14 @@ where
 
 #[cfg_attr(docsrs, doc(cfg(feature = "extract_if")))]
 #[cfg(feature = "extract_if")]
-impl<K, V, F, const N: usize> FusedIterator for ExtractIf<'_, K, V, F, N> where
+impl<K, V, F, const N: usize, A: Allocator> FusedIterator for ExtractIf<'_, K, V, F, N, A> where
     F: FnMut(&K, &mut V) -> bool
 {
 }
 
 #[cfg_attr(docsrs, doc(cfg(feature = "extract_if")))]
 #[cfg(feature = "extract_if")]
-impl<'a, K, V, F, const N: usize> Debug for ExtractIf<'a, K, V, F, N>
+impl<'a, K, V, F, const N: usize, A: Allocator> Debug for ExtractIf<'a, K, V, F, N, A>
 where
     F: FnMut(&K, &mut V) -> bool,
 {
@@ -1840,9 +2858,9 @@ where
     }
 }
 
-impl<K, V, const N: usize> IntoIterator for CompactMap<K, V, N> {
+impl<K, V, const N: usize, A: Allocator> IntoIterator for CompactMap<K, V, N, A> {
     type Item = (K, V);
-    type IntoIter = IntoIter<K, V, N>;
+    type IntoIter = IntoIter<K, V, N, A>;
 
     /// Creates a consuming iterator, that is, one that moves each key-value
     /// pair out of the map in arbitrary order. The map cannot be used after
@@ -1863,7 +2881,7 @@ impl<K, V, const N: usize> IntoIterator for CompactMap<K, V, N> {
     /// let vec: Vec<(&str, i32)> = map.into_iter().collect();
     /// ```
     #[inline]
-    fn into_iter(self) -> IntoIter<K, V, N> {
+    fn into_iter(self) -> IntoIter<K, V, N, A> {
         IntoIter {
             base: self.base.into_iter(),
         }
@@ -1881,10 +2899,25 @@ where
     }
 }
 
-impl<K, V, const N: usize> Extend<(K, V)> for CompactMap<K, V, N>
+impl<K, V, const N: usize, A: Allocator + Clone> Extend<(K, V)> for CompactMap<K, V, N, A>
 where
     K: Eq + Hash,
 {
+    /// Extends the map with the key-value pairs of an iterator.
+    ///
+    /// If the iterator's lower bound doesn't fit inline, the map spills straight to a
+    /// [`HashMap`] sized for it, so it spills at most once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::CompactMap;
+    ///
+    /// let mut map: CompactMap<i32, i32, 4> = CompactMap::new();
+    /// map.extend((0..100).map(|x| (x, x)));
+    /// assert!(map.spilled());
+    /// assert!(map.capacity() >= 100);
+    /// ```
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
         self.base.extend(iter);
     }
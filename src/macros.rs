@@ -0,0 +1,81 @@
+/// Creates a [`CompactMap`](crate::CompactMap) containing the given key-value pairs.
+///
+/// The inline capacity `N` defaults to the number of pairs, or to the crate default of 16,
+/// whichever is larger, so the map never spills on construction.
+/// An explicit capacity can be given with a leading `N = ...;`.
+///
+/// # Panics
+///
+/// Panics if two keys compare equal. When all keys are literals, identical literals are
+/// rejected at compile time instead.
+///
+/// # Examples
+///
+/// ```
+/// use compact_map::{compact_map, CompactMap};
+///
+/// let map = compact_map! {
+///     "a" => 1,
+///     "b" => 2,
+/// };
+/// assert_eq!(map["a"], 1);
+/// assert_eq!(map.capacity(), 16);
+///
+/// let map: CompactMap<_, _, 2> = compact_map! { N = 2; "a" => 1, "b" => 2 };
+/// assert!(!map.spilled());
+/// ```
+///
+/// Duplicate literal keys fail to compile:
+///
+/// ```compile_fail
+/// use compact_map::compact_map;
+///
+/// let map = compact_map! { "a" => 1, "a" => 2 };
+/// ```
+///
+/// Duplicate non-literal keys panic:
+///
+/// ```should_panic
+/// use compact_map::compact_map;
+///
+/// let key = "a";
+/// let map = compact_map! { key => 1, "a" => 2 };
+/// ```
+#[macro_export]
+macro_rules! compact_map {
+    (@insert $n:expr; $($key:expr => $value:expr),*) => {{
+        let mut map = $crate::CompactMap::<_, _, { $n }>::new();
+        $(
+            if map.insert($key, $value).is_some() {
+                panic!(concat!("duplicate key in `compact_map!`: ", stringify!($key)));
+            }
+        )*
+        map
+    }};
+    (@check $($key:literal),*) => {
+        const _: () = assert!(
+            !$crate::__private::has_duplicate_literals(&[$(stringify!($key)),*]),
+            "duplicate literal key in `compact_map!`",
+        );
+    };
+    (N = $n:expr; $($key:literal => $value:expr),* $(,)?) => {{
+        $crate::compact_map!(@check $($key),*);
+        $crate::compact_map!(@insert $n; $($key => $value),*)
+    }};
+    (N = $n:expr; $($key:expr => $value:expr),* $(,)?) => {
+        $crate::compact_map!(@insert $n; $($key => $value),*)
+    };
+    ($($key:literal => $value:expr),* $(,)?) => {{
+        $crate::compact_map!(@check $($key),*);
+        $crate::compact_map!(
+            @insert $crate::__private::inline_capacity(&[$(stringify!($key)),*]);
+            $($key => $value),*
+        )
+    }};
+    ($($key:expr => $value:expr),* $(,)?) => {
+        $crate::compact_map!(
+            @insert $crate::__private::inline_capacity(&[$(stringify!($key)),*]);
+            $($key => $value),*
+        )
+    };
+}
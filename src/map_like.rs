@@ -0,0 +1,467 @@
+use crate::{Allocator, CompactMap, Entry, Lookup};
+use std::borrow::Borrow;
+use std::collections::{btree_map, hash_map, BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+/// Read-only access to a map, abstracting over the backing map type.
+///
+/// Implemented for [`CompactMap`], [`HashMap`], [`BTreeMap`] and [`heapless::LinearMap`],
+/// so generic code can be written once for any of them.
+///
+/// Lookups take a `&Q`, which defaults to the key type. Any borrowed form of the key
+/// that the backing map accepts works too, such as `str` for `String` keys.
+///
+/// # Examples
+///
+/// ```
+/// use compact_map::{CompactMap, MapLike};
+/// use std::collections::BTreeMap;
+///
+/// fn total<M: MapLike<&'static str, u32>>(map: &M) -> u32 {
+///     map.iter().map(|(_, v)| *v).sum()
+/// }
+///
+/// let compact: CompactMap<_, _, 4> = CompactMap::from([("a", 1), ("b", 2)]);
+/// let btree = BTreeMap::from([("a", 1), ("b", 2)]);
+/// assert_eq!(total(&compact), 3);
+/// assert_eq!(total(&btree), 3);
+/// ```
+///
+/// Looking up `String` keys by `&str`:
+///
+/// ```
+/// use compact_map::{CompactMap, MapLike};
+/// use std::collections::HashMap;
+///
+/// fn has_admin<M: MapLike<String, u32, str>>(map: &M) -> bool {
+///     map.contains_key("admin")
+/// }
+///
+/// let mut compact: CompactMap<String, u32, 4> = CompactMap::new();
+/// compact.insert("admin".to_string(), 0);
+/// let hash = HashMap::from([("guest".to_string(), 1)]);
+/// assert!(has_admin(&compact));
+/// assert!(!has_admin(&hash));
+/// ```
+pub trait MapLike<K, V, Q: ?Sized = K> {
+    /// The iterator returned by [`iter`](MapLike::iter).
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    /// Returns the number of elements in the map.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the map contains no elements.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    fn get(&self, key: &Q) -> Option<&V>;
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    fn contains_key(&self, key: &Q) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// An iterator visiting all key-value pairs.
+    /// The iteration order is the one of the backing map.
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+/// Mutable access to a map, abstracting over the backing map type.
+///
+/// Implemented for [`CompactMap`], [`HashMap`] and [`BTreeMap`]. [`heapless::LinearMap`]
+/// only implements [`MapLike`], as it can't take a new key once it is full.
+///
+/// # Examples
+///
+/// ```
+/// use compact_map::{CompactMap, MapLikeMut};
+/// use std::collections::HashMap;
+///
+/// fn bump<M: MapLikeMut<char, u32>>(map: &mut M, key: char) {
+///     match map.get_mut(&key) {
+///         Some(v) => *v += 1,
+///         None => {
+///             map.insert(key, 1);
+///         }
+///     }
+/// }
+///
+/// let mut compact: CompactMap<char, u32, 4> = CompactMap::new();
+/// let mut hash = HashMap::new();
+/// for ch in "hello".chars() {
+///     bump(&mut compact, ch);
+///     bump(&mut hash, ch);
+/// }
+/// assert_eq!(compact[&'l'], 2);
+/// assert_eq!(hash[&'l'], 2);
+/// ```
+pub trait MapLikeMut<K, V, Q: ?Sized = K>: MapLike<K, V, Q> {
+    /// Returns a mutable reference to the value corresponding to the key.
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V>;
+
+    /// Inserts a key-value pair into the map, returning the old value if the key was present.
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    fn remove(&mut self, key: &Q) -> Option<V>;
+
+    /// Clears the map, removing all key-value pairs.
+    fn clear(&mut self);
+}
+
+/// Access to the entry API of a map, abstracting over the backing map type.
+///
+/// # Examples
+///
+/// ```
+/// use compact_map::{CompactMap, MapEntry, MapLikeEntry};
+/// use std::collections::BTreeMap;
+///
+/// fn count<M: MapLikeEntry<char, u32>>(map: &mut M, text: &str) {
+///     for ch in text.chars() {
+///         map.entry(ch).and_modify(|c| *c += 1).or_insert(1);
+///     }
+/// }
+///
+/// let mut compact: CompactMap<char, u32, 4> = CompactMap::new();
+/// let mut btree = BTreeMap::new();
+/// count(&mut compact, "a short treatise on fungi");
+/// count(&mut btree, "a short treatise on fungi");
+/// assert_eq!(compact[&'t'], 3);
+/// assert_eq!(btree[&'t'], 3);
+/// ```
+pub trait MapLikeEntry<K, V>: MapLikeMut<K, V> {
+    /// The entry type returned by [`entry`](MapLikeEntry::entry).
+    type Entry<'a>: MapEntry<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    fn entry(&mut self, key: K) -> Self::Entry<'_>;
+}
+
+/// A view into a single entry in a map, which may either be vacant or occupied.
+///
+/// This is the common subset of the entry APIs of the maps implementing [`MapLikeEntry`].
+pub trait MapEntry<'a, K: 'a, V: 'a>: Sized {
+    /// Returns a reference to this entry's key.
+    fn key(&self) -> &K;
+
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry.
+    fn or_insert(self, default: V) -> &'a mut V;
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V;
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of the default function,
+    /// which is given a reference to the entry's key.
+    fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V;
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self;
+
+    /// Ensures a value is in the entry by inserting the default value if empty,
+    /// and returns a mutable reference to the value in the entry.
+    #[inline]
+    fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<K, V, Q, const N: usize, A: Allocator + Clone> MapLike<K, V, Q> for CompactMap<K, V, N, A>
+where
+    K: Eq + Hash,
+    Q: Lookup<K> + ?Sized,
+{
+    type Iter<'a> = crate::Iter<'a, K, V, N> where Self: 'a, K: 'a, V: 'a;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len()
+    }
+    #[inline]
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+    #[inline]
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+}
+
+impl<K, V, Q, const N: usize, A: Allocator + Clone> MapLikeMut<K, V, Q> for CompactMap<K, V, N, A>
+where
+    K: Eq + Hash,
+    Q: Lookup<K> + ?Sized,
+{
+    #[inline]
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+    #[inline]
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+    #[inline]
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<K, V, const N: usize, A: Allocator + Clone> MapLikeEntry<K, V> for CompactMap<K, V, N, A>
+where
+    K: Eq + Hash,
+{
+    type Entry<'a> = Entry<'a, K, V, N, A> where Self: 'a, K: 'a, V: 'a;
+
+    #[inline]
+    fn entry(&mut self, key: K) -> Self::Entry<'_> {
+        self.entry(key)
+    }
+}
+
+impl<'a, K: 'a, V: 'a, const N: usize, A> MapEntry<'a, K, V> for Entry<'a, K, V, N, A>
+where
+    K: Eq + Hash,
+    A: Allocator + Clone,
+{
+    #[inline]
+    fn key(&self) -> &K {
+        self.key()
+    }
+    #[inline]
+    fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert(default)
+    }
+    #[inline]
+    fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with(default)
+    }
+    #[inline]
+    fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(default)
+    }
+    #[inline]
+    fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        self.and_modify(f)
+    }
+}
+
+impl<K, V, Q, S> MapLike<K, V, Q> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Iter<'a> = hash_map::Iter<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len()
+    }
+    #[inline]
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+    #[inline]
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+}
+
+impl<K, V, Q, S> MapLikeMut<K, V, Q> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    #[inline]
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+    #[inline]
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+    #[inline]
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<K, V, S> MapLikeEntry<K, V> for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    type Entry<'a> = hash_map::Entry<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+
+    #[inline]
+    fn entry(&mut self, key: K) -> Self::Entry<'_> {
+        self.entry(key)
+    }
+}
+
+impl<'a, K: 'a, V: 'a> MapEntry<'a, K, V> for hash_map::Entry<'a, K, V> {
+    #[inline]
+    fn key(&self) -> &K {
+        self.key()
+    }
+    #[inline]
+    fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert(default)
+    }
+    #[inline]
+    fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with(default)
+    }
+    #[inline]
+    fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(default)
+    }
+    #[inline]
+    fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        self.and_modify(f)
+    }
+}
+
+impl<K, V, Q> MapLike<K, V, Q> for BTreeMap<K, V>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    type Iter<'a> = btree_map::Iter<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len()
+    }
+    #[inline]
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+    #[inline]
+    fn contains_key(&self, key: &Q) -> bool {
+        self.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+}
+
+impl<K, V, Q> MapLikeMut<K, V, Q> for BTreeMap<K, V>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    #[inline]
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+    #[inline]
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+    #[inline]
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        self.remove(key)
+    }
+    #[inline]
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<K, V> MapLikeEntry<K, V> for BTreeMap<K, V>
+where
+    K: Ord,
+{
+    type Entry<'a> = btree_map::Entry<'a, K, V> where Self: 'a, K: 'a, V: 'a;
+
+    #[inline]
+    fn entry(&mut self, key: K) -> Self::Entry<'_> {
+        self.entry(key)
+    }
+}
+
+impl<'a, K: 'a + Ord, V: 'a> MapEntry<'a, K, V> for btree_map::Entry<'a, K, V> {
+    #[inline]
+    fn key(&self) -> &K {
+        self.key()
+    }
+    #[inline]
+    fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert(default)
+    }
+    #[inline]
+    fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with(default)
+    }
+    #[inline]
+    fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(default)
+    }
+    #[inline]
+    fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        self.and_modify(f)
+    }
+}
+
+impl<K, V, Q, const N: usize> MapLike<K, V, Q> for heapless::LinearMap<K, V, N>
+where
+    K: Eq + Borrow<Q>,
+    Q: Eq + ?Sized,
+{
+    type Iter<'a> = <&'a heapless::LinearMap<K, V, N> as IntoIterator>::IntoIter
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len()
+    }
+    #[inline]
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.get(key)
+    }
+    #[inline]
+    fn iter(&self) -> Self::Iter<'_> {
+        self.into_iter()
+    }
+}
@@ -0,0 +1,62 @@
+//! [`proptest`](::proptest) strategies for [`CompactMap`].
+
+use crate::CompactMap;
+use ::proptest::collection::{hash_map, SizeRange};
+use ::proptest::prelude::*;
+use std::hash::Hash;
+use std::sync::Arc;
+
+/// Creates a strategy to generate [`CompactMap`]s with keys and values drawn from `key` and
+/// `value`, and with a size within `size`.
+///
+/// Half of the maps have a size near the inline capacity, from `N - 1` to `N + 2` where `size`
+/// allows it, so that tests exercise both sides of the spill. A quarter of the maps are spilled
+/// even if they fit inline, since a map doesn't move back inline until it is shrunk.
+///
+/// Like [`hash_map`], this rejects maps with fewer than `size.start()` distinct keys.
+///
+/// # Examples
+///
+/// ```
+/// use compact_map::CompactMap;
+/// use proptest::prelude::*;
+///
+/// proptest! {
+///     fn len_matches_iter(map in compact_map::proptest::compact_map::<_, _, 4>(any::<u8>(), any::<u8>(), 0..8)) {
+///         prop_assert_eq!(map.len(), map.iter().count());
+///     }
+/// }
+/// # len_matches_iter();
+/// ```
+pub fn compact_map<K, V, const N: usize>(
+    key: K,
+    value: V,
+    size: impl Into<SizeRange>,
+) -> impl Strategy<Value = CompactMap<K::Value, V::Value, N>>
+where
+    K: Strategy,
+    K::Value: Eq + Hash,
+    V: Strategy,
+{
+    let size = size.into();
+    let (start, end) = size.start_end_incl();
+    let near = N.saturating_sub(1).max(start)..=N.saturating_add(2).min(end);
+    let near = if near.is_empty() {
+        size.clone()
+    } else {
+        near.into()
+    };
+
+    let (key, value) = (Arc::new(key), Arc::new(value));
+    let maps = prop_oneof![
+        hash_map(Arc::clone(&key), Arc::clone(&value), size),
+        hash_map(key, value, near),
+    ];
+    (maps, prop::bool::weighted(0.25)).prop_map(|(entries, spill)| {
+        let mut map: CompactMap<_, _, N> = entries.into_iter().collect();
+        if spill {
+            map.spill();
+        }
+        map
+    })
+}
@@ -0,0 +1,969 @@
+use crate::base::spilled::{self, hash_map, Global, HashMap};
+use crate::DEFAULT_MAX_INLINE_ENTRIES;
+use std::borrow::Borrow;
+use std::fmt;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FusedIterator;
+use std::mem::{self, ManuallyDrop, MaybeUninit};
+use std::ops::Index;
+
+/// A map like [`CompactMap`](crate::CompactMap) whose inline entries never move.
+///
+/// Inline entries live in fixed slots, and removed slots are put on a free list instead of
+/// being filled by the last entry. This lets [`insert`](Self::insert) return a [`Handle`]
+/// to the entry, which gives O(1) access to it for as long as it stays in the map.
+///
+/// Every inline insertion is stamped with a new generation, so a handle is rejected once its
+/// entry is removed, even if its slot is reused. Handles are also rejected once the map has
+/// spilled into a `HashMap`, and stay rejected if it moves back inline.
+///
+/// # Examples
+///
+/// ```
+/// use compact_map::StableCompactMap;
+///
+/// let mut map: StableCompactMap<&str, u32, 4> = StableCompactMap::new();
+/// let (a, _) = map.insert("a", 1);
+/// let (b, _) = map.insert("b", 2);
+///
+/// map.remove("a");
+/// assert_eq!(map.get_by_handle(a), None);
+/// assert_eq!(map.get_by_handle(b), Some(&2));
+///
+/// // the slot of "a" is reused, but not its handle
+/// let (c, _) = map.insert("c", 3);
+/// assert_eq!(map.get_by_handle(a), None);
+/// assert_eq!(map.get_by_handle(c), Some(&3));
+/// ```
+#[derive(Clone)]
+pub struct StableCompactMap<K, V, const N: usize> {
+    inner: StableInner<K, V, N>,
+    // generation of the next inline insertion; handles of spilled entries have generation 0
+    next_generation: u64,
+}
+
+#[derive(Clone)]
+enum StableInner<K, V, const N: usize> {
+    Inline {
+        slots: [Slot<K, V>; N],
+        // index of the first vacant slot, `N` if there is none
+        free: usize,
+        len: usize,
+    },
+    // boxed like `MapImpl::Spilled`, so the map is no larger than its slots
+    #[allow(clippy::box_collection)]
+    Spilled(Box<HashMap<K, V, Global>>),
+}
+
+#[derive(Clone)]
+enum Slot<K, V> {
+    Occupied { key: K, value: V, generation: u64 },
+    Vacant { next_free: usize },
+}
+
+/// A handle to an entry of a [`StableCompactMap`], returned by
+/// [`insert`](StableCompactMap::insert).
+///
+/// It stays valid until the entry is removed, or the map spills.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Handle {
+    index: usize,
+    generation: u64,
+}
+
+impl Handle {
+    /// The handle of entries inserted while spilled, which is never valid.
+    const DETACHED: Self = Self {
+        index: usize::MAX,
+        generation: 0,
+    };
+}
+
+impl<K, V, const N: usize> StableCompactMap<K, V, N> {
+    /// Creates an empty `StableCompactMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    /// let mut map: StableCompactMap<&str, i32, 16> = StableCompactMap::new();
+    ///
+    /// // it can be used in constants
+    /// const EMPTY: StableCompactMap<u32, u32, 4> = StableCompactMap::new();
+    /// assert!(EMPTY.is_empty());
+    /// ```
+    pub const fn new() -> Self {
+        Self {
+            inner: StableInner::Inline {
+                slots: vacant_slots(),
+                free: 0,
+                len: 0,
+            },
+            next_generation: 1,
+        }
+    }
+
+    /// Returns `true` if the data has spilled into a `HashMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, i32, 2> = StableCompactMap::new();
+    /// map.insert(1, 1);
+    /// map.insert(2, 2);
+    /// assert!(!map.spilled());
+    /// map.insert(3, 3);
+    /// assert!(map.spilled());
+    /// ```
+    #[inline]
+    pub fn spilled(&self) -> bool {
+        matches!(self.inner, StableInner::Spilled(_))
+    }
+
+    /// Returns the number of elements in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match &self.inner {
+            StableInner::Inline { len, .. } => *len,
+            StableInner::Spilled(map) => map.len(),
+        }
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<&str, i32, 4> = StableCompactMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// let mut pairs: Vec<_> = map.iter().collect();
+    /// pairs.sort_unstable();
+    /// assert_eq!(pairs, [(&"a", &1), (&"b", &2)]);
+    /// ```
+    #[inline]
+    pub fn iter(&self) -> StableIter<'_, K, V> {
+        let inner = match &self.inner {
+            StableInner::Inline { slots, len, .. } => IterInner::Inline {
+                slots: slots.iter(),
+                remaining: *len,
+            },
+            StableInner::Spilled(map) => IterInner::Spilled(map.iter()),
+        };
+        StableIter { inner }
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order,
+    /// with mutable references to the values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<&str, i32, 4> = StableCompactMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// for (_, value) in map.iter_mut() {
+    ///     *value *= 10;
+    /// }
+    /// assert_eq!(map["a"], 10);
+    /// assert_eq!(map["b"], 20);
+    /// ```
+    #[inline]
+    pub fn iter_mut(&mut self) -> StableIterMut<'_, K, V> {
+        let inner = match &mut self.inner {
+            StableInner::Inline { slots, len, .. } => IterMutInner::Inline {
+                slots: slots.iter_mut(),
+                remaining: *len,
+            },
+            StableInner::Spilled(map) => IterMutInner::Spilled(map.iter_mut()),
+        };
+        StableIterMut { inner }
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    ///
+    /// A spilled map stays spilled and keeps its capacity. Every handle is invalidated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, &str, 4> = StableCompactMap::new();
+    /// let (one, _) = map.insert(1, "a");
+    /// map.clear();
+    /// assert!(map.is_empty());
+    /// assert_eq!(map.get_by_handle(one), None);
+    ///
+    /// // the slot is reused, but the handle stays invalid
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get_by_handle(one), None);
+    /// ```
+    pub fn clear(&mut self) {
+        match &mut self.inner {
+            StableInner::Inline { slots, free, len } => {
+                // if a drop panics, the slots are still all vacant afterwards
+                *free = 0;
+                *len = 0;
+                *slots = vacant_slots();
+            }
+            StableInner::Spilled(map) => map.clear(),
+        }
+    }
+
+    /// Returns a reference to the value of the entry `handle` refers to, if it is still valid.
+    ///
+    /// This is O(1).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, &str, 2> = StableCompactMap::new();
+    /// let (one, _) = map.insert(1, "a");
+    /// assert_eq!(map.get_by_handle(one), Some(&"a"));
+    ///
+    /// // spilling invalidates every handle
+    /// map.insert(2, "b");
+    /// map.insert(3, "c");
+    /// assert_eq!(map.get_by_handle(one), None);
+    /// ```
+    #[inline]
+    pub fn get_by_handle(&self, handle: Handle) -> Option<&V> {
+        match &self.inner {
+            StableInner::Inline { slots, .. } => match slots.get(handle.index)? {
+                Slot::Occupied {
+                    value, generation, ..
+                } if *generation == handle.generation => Some(value),
+                _ => None,
+            },
+            StableInner::Spilled(_) => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value of the entry `handle` refers to,
+    /// if it is still valid.
+    ///
+    /// This is O(1).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, i32, 4> = StableCompactMap::new();
+    /// let (one, _) = map.insert(1, 10);
+    /// *map.get_by_handle_mut(one).unwrap() += 1;
+    /// assert_eq!(map.get_by_handle(one), Some(&11));
+    /// ```
+    #[inline]
+    pub fn get_by_handle_mut(&mut self, handle: Handle) -> Option<&mut V> {
+        match &mut self.inner {
+            StableInner::Inline { slots, .. } => match slots.get_mut(handle.index)? {
+                Slot::Occupied {
+                    value, generation, ..
+                } if *generation == handle.generation => Some(value),
+                _ => None,
+            },
+            StableInner::Spilled(_) => None,
+        }
+    }
+
+    /// Removes the entry `handle` refers to and returns it, if the handle is still valid.
+    ///
+    /// This is O(1).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, &str, 4> = StableCompactMap::new();
+    /// let (one, _) = map.insert(1, "a");
+    /// assert_eq!(map.remove_by_handle(one), Some((1, "a")));
+    /// assert_eq!(map.remove_by_handle(one), None);
+    /// ```
+    #[inline]
+    pub fn remove_by_handle(&mut self, handle: Handle) -> Option<(K, V)> {
+        match &self.inner {
+            StableInner::Inline { slots, .. } => match slots.get(handle.index)? {
+                Slot::Occupied { generation, .. } if *generation == handle.generation => {
+                    Some(self.remove_slot(handle.index))
+                }
+                _ => None,
+            },
+            StableInner::Spilled(_) => None,
+        }
+    }
+
+    /// Empties the occupied slot at `index`, and puts it on the free list.
+    fn remove_slot(&mut self, index: usize) -> (K, V) {
+        let StableInner::Inline { slots, free, len } = &mut self.inner else {
+            unreachable!("remove_slot on a spilled map");
+        };
+        let vacant = Slot::Vacant { next_free: *free };
+        match mem::replace(&mut slots[index], vacant) {
+            Slot::Occupied { key, value, .. } => {
+                *free = index;
+                *len -= 1;
+                (key, value)
+            }
+            Slot::Vacant { .. } => unreachable!("remove_slot on a vacant slot"),
+        }
+    }
+}
+
+impl<K, V, const N: usize> StableCompactMap<K, V, N>
+where
+    K: Eq + Hash,
+{
+    /// Inserts a key-value pair into the map, and returns a [`Handle`] to its entry
+    /// along with the value previously stored for the key, if any.
+    ///
+    /// If the key was already in the map, its entry keeps its slot and its handle.
+    /// Entries inserted while the map is spilled get a handle that is never valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, &str, 4> = StableCompactMap::new();
+    /// let (first, old) = map.insert(37, "a");
+    /// assert_eq!(old, None);
+    ///
+    /// let (second, old) = map.insert(37, "b");
+    /// assert_eq!(old, Some("a"));
+    /// assert_eq!(first, second);
+    /// assert_eq!(map.get_by_handle(first), Some(&"b"));
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> (Handle, Option<V>) {
+        if let StableInner::Inline { slots, free, len } = &mut self.inner {
+            if let Some(index) = find_slot(slots, &key) {
+                let Slot::Occupied {
+                    value: old,
+                    generation,
+                    ..
+                } = &mut slots[index]
+                else {
+                    unreachable!("find_slot returned a vacant slot");
+                };
+                let handle = Handle {
+                    index,
+                    generation: *generation,
+                };
+                return (handle, Some(mem::replace(old, value)));
+            }
+            if *free < N {
+                let index = *free;
+                let Slot::Vacant { next_free } = slots[index] else {
+                    unreachable!("occupied slot on the free list");
+                };
+                let generation = self.next_generation;
+                self.next_generation += 1;
+                slots[index] = Slot::Occupied {
+                    key,
+                    value,
+                    generation,
+                };
+                *free = next_free;
+                *len += 1;
+                return (Handle { index, generation }, None);
+            }
+            self.spill();
+        }
+        match &mut self.inner {
+            StableInner::Spilled(map) => (Handle::DETACHED, map.insert(key, value)),
+            StableInner::Inline { .. } => unreachable!("the map just spilled"),
+        }
+    }
+
+    /// Returns the [`Handle`] of the entry for `key`, if the key is in the map and inline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<&str, i32, 4> = StableCompactMap::new();
+    /// let (a, _) = map.insert("a", 1);
+    /// assert_eq!(map.handle_of("a"), Some(a));
+    /// assert_eq!(map.handle_of("b"), None);
+    /// ```
+    pub fn handle_of<Q>(&self, k: &Q) -> Option<Handle>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &self.inner {
+            StableInner::Inline { slots, .. } => {
+                let index = find_slot(slots, k)?;
+                match &slots[index] {
+                    Slot::Occupied { generation, .. } => Some(Handle {
+                        index,
+                        generation: *generation,
+                    }),
+                    Slot::Vacant { .. } => None,
+                }
+            }
+            StableInner::Spilled(_) => None,
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, &str, 4> = StableCompactMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some(&"a"));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &self.inner {
+            StableInner::Inline { slots, .. } => match &slots[find_slot(slots, k)?] {
+                Slot::Occupied { value, .. } => Some(value),
+                Slot::Vacant { .. } => None,
+            },
+            StableInner::Spilled(map) => map.get(k),
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, &str, 4> = StableCompactMap::new();
+    /// map.insert(1, "a");
+    /// if let Some(x) = map.get_mut(&1) {
+    ///     *x = "b";
+    /// }
+    /// assert_eq!(map.get(&1), Some(&"b"));
+    /// ```
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &mut self.inner {
+            StableInner::Inline { slots, .. } => {
+                let index = find_slot(slots, k)?;
+                match &mut slots[index] {
+                    Slot::Occupied { value, .. } => Some(value),
+                    Slot::Vacant { .. } => None,
+                }
+            }
+            StableInner::Spilled(map) => map.get_mut(k),
+        }
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(k).is_some()
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    ///
+    /// The other entries keep their slots and their handles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, &str, 4> = StableCompactMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.remove(&1), Some("a"));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    #[inline]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(k).map(|(_, v)| v)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    #[inline]
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match &mut self.inner {
+            StableInner::Inline { slots, .. } => {
+                let index = find_slot(slots, k)?;
+                Some(self.remove_slot(index))
+            }
+            StableInner::Spilled(map) => map.remove_entry(k),
+        }
+    }
+
+    /// Shrinks the capacity of the map as much as possible, moving the entries back inline
+    /// if they fit.
+    ///
+    /// Entries moved back inline get new handles, which [`handle_of`](Self::handle_of) returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, i32, 2> = StableCompactMap::new();
+    /// for i in 0..3 {
+    ///     map.insert(i, i);
+    /// }
+    /// map.remove(&0);
+    /// map.shrink_to_fit();
+    ///
+    /// assert!(!map.spilled());
+    /// let handle = map.handle_of(&2).unwrap();
+    /// assert_eq!(map.get_by_handle(handle), Some(&2));
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        if let StableInner::Spilled(map) = &mut self.inner {
+            if map.len() > N {
+                map.shrink_to_fit();
+                return;
+            }
+            let map = mem::take(map.as_mut());
+            *self = Self {
+                next_generation: self.next_generation,
+                ..Self::new()
+            };
+            for (k, v) in map {
+                self.insert(k, v);
+            }
+        }
+    }
+
+    /// Moves every entry into a `HashMap`.
+    ///
+    /// Each key is hashed while its entry is still in its slot, and the entry is then
+    /// inserted with that hash into the table reserved up front, which can't fail. So if
+    /// `Hash` panics, every entry is still inline, see [`SpillGuard`].
+    fn spill(&mut self) {
+        let StableInner::Inline { slots, free, len } = &mut self.inner else {
+            return;
+        };
+        let mut map = spilled::with_capacity_in(N + 1, Global);
+        let guard = SpillGuard {
+            slots,
+            free,
+            len,
+            next_generation: &mut self.next_generation,
+            map: &mut map,
+        };
+        for slot in guard.slots.iter_mut() {
+            let Slot::Occupied { key, .. } = slot else {
+                continue;
+            };
+            let hash = guard.map.hasher().hash_one(&*key);
+            let Slot::Occupied { key, value, .. } =
+                mem::replace(slot, Slot::Vacant { next_free: N })
+            else {
+                unreachable!("the slot was just checked");
+            };
+            // Safety: room for every slot was reserved above
+            unsafe { guard.map.raw_table_mut().insert_no_grow(hash, (key, value)) };
+        }
+        mem::forget(guard);
+        self.inner = StableInner::Spilled(Box::new(map));
+    }
+}
+
+/// Puts the entries moved into `map` back into vacant slots if `spill` unwinds, and
+/// rebuilds the free list and length from the slots.
+///
+/// The entries moved back get new generations, as spilling invalidates handles anyway.
+struct SpillGuard<'a, K, V, const N: usize> {
+    slots: &'a mut [Slot<K, V>; N],
+    free: &'a mut usize,
+    len: &'a mut usize,
+    next_generation: &'a mut u64,
+    map: &'a mut HashMap<K, V, Global>,
+}
+
+impl<K, V, const N: usize> Drop for SpillGuard<'_, K, V, N> {
+    fn drop(&mut self) {
+        let mut moved = self.map.drain();
+        for slot in self.slots.iter_mut() {
+            if let Slot::Vacant { .. } = slot {
+                let Some((key, value)) = moved.next() else {
+                    break;
+                };
+                let generation = *self.next_generation;
+                *self.next_generation += 1;
+                *slot = Slot::Occupied {
+                    key,
+                    value,
+                    generation,
+                };
+            }
+        }
+        *self.free = N;
+        *self.len = 0;
+        for (index, slot) in self.slots.iter_mut().enumerate().rev() {
+            match slot {
+                Slot::Occupied { .. } => *self.len += 1,
+                Slot::Vacant { next_free } => {
+                    *next_free = *self.free;
+                    *self.free = index;
+                }
+            }
+        }
+    }
+}
+
+/// `N` vacant slots, each linked to the next one.
+const fn vacant_slots<K, V, const N: usize>() -> [Slot<K, V>; N] {
+    // `array::from_fn` isn't const, so the slots are written one by one
+    // Safety: an array of `MaybeUninit` needs no initialization
+    let mut slots: [MaybeUninit<Slot<K, V>>; N] = unsafe { MaybeUninit::uninit().assume_init() };
+    let mut index = 0;
+    while index < N {
+        slots[index] = MaybeUninit::new(Slot::Vacant {
+            next_free: index + 1,
+        });
+        index += 1;
+    }
+    // `transmute` can't tell that the two arrays have the same size
+    union Slots<K, V, const N: usize> {
+        uninit: ManuallyDrop<[MaybeUninit<Slot<K, V>>; N]>,
+        init: ManuallyDrop<[Slot<K, V>; N]>,
+    }
+    let slots = Slots {
+        uninit: ManuallyDrop::new(slots),
+    };
+    // Safety: every slot is initialized, and `MaybeUninit<T>` has the layout of `T`
+    ManuallyDrop::into_inner(unsafe { slots.init })
+}
+
+/// Index of the occupied slot holding `k`.
+#[inline]
+fn find_slot<K, V, Q>(slots: &[Slot<K, V>], k: &Q) -> Option<usize>
+where
+    K: Borrow<Q>,
+    Q: Eq + ?Sized,
+{
+    slots.iter().position(|slot| match slot {
+        Slot::Occupied { key, .. } => key.borrow() == k,
+        Slot::Vacant { .. } => false,
+    })
+}
+
+impl<K, V> Default for StableCompactMap<K, V, DEFAULT_MAX_INLINE_ENTRIES> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> Debug for StableCompactMap<K, V, N>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, Q: ?Sized, V, const N: usize> Index<&Q> for StableCompactMap<K, V, N>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `StableCompactMap`.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, const N: usize> FromIterator<(K, V)> for StableCompactMap<K, V, N>
+where
+    K: Eq + Hash,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, const N: usize> Extend<(K, V)> for StableCompactMap<K, V, N>
+where
+    K: Eq + Hash,
+{
+    /// Extends the map with the key-value pairs of an iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<i32, i32, 4> = (0..2).map(|x| (x, x)).collect();
+    /// map.extend((2..4).map(|x| (x, x)));
+    /// assert!(!map.spilled());
+    /// assert_eq!(map.len(), 4);
+    /// assert!(map.handle_of(&3).is_some());
+    /// ```
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, const N: usize> IntoIterator for &'a StableCompactMap<K, V, N> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = StableIter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> StableIter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, const N: usize> IntoIterator for &'a mut StableCompactMap<K, V, N> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = StableIterMut<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> StableIterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, const N: usize> IntoIterator for StableCompactMap<K, V, N> {
+    type Item = (K, V);
+    type IntoIter = StableIntoIter<K, V, N>;
+
+    /// Creates a consuming iterator, that is, one that moves each key-value
+    /// pair out of the map in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use compact_map::StableCompactMap;
+    ///
+    /// let mut map: StableCompactMap<&str, i32, 4> = StableCompactMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// let mut pairs: Vec<(&str, i32)> = map.into_iter().collect();
+    /// pairs.sort_unstable();
+    /// assert_eq!(pairs, [("a", 1), ("b", 2)]);
+    /// ```
+    #[inline]
+    fn into_iter(self) -> StableIntoIter<K, V, N> {
+        let inner = match self.inner {
+            StableInner::Inline { slots, len, .. } => IntoIterInner::Inline {
+                slots: slots.into_iter(),
+                remaining: len,
+            },
+            StableInner::Spilled(map) => IntoIterInner::Spilled(map.into_iter()),
+        };
+        StableIntoIter { inner }
+    }
+}
+
+/// An iterator over the entries of a [`StableCompactMap`].
+///
+/// This `struct` is created by the [`iter`](StableCompactMap::iter) method on
+/// [`StableCompactMap`].
+pub struct StableIter<'a, K, V> {
+    inner: IterInner<'a, K, V>,
+}
+
+enum IterInner<'a, K, V> {
+    Inline {
+        slots: std::slice::Iter<'a, Slot<K, V>>,
+        remaining: usize,
+    },
+    Spilled(hash_map::Iter<'a, K, V>),
+}
+
+impl<'a, K, V> Iterator for StableIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            IterInner::Inline { slots, remaining } => {
+                let item = slots.find_map(|slot| match slot {
+                    Slot::Occupied { key, value, .. } => Some((key, value)),
+                    Slot::Vacant { .. } => None,
+                })?;
+                *remaining -= 1;
+                Some(item)
+            }
+            IterInner::Spilled(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            IterInner::Inline { remaining, .. } => (*remaining, Some(*remaining)),
+            IterInner::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for StableIter<'_, K, V> {}
+
+impl<K, V> FusedIterator for StableIter<'_, K, V> {}
+
+impl<K, V> Debug for StableIter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StableIter").finish_non_exhaustive()
+    }
+}
+
+/// A mutable iterator over the entries of a [`StableCompactMap`].
+///
+/// This `struct` is created by the [`iter_mut`](StableCompactMap::iter_mut) method on
+/// [`StableCompactMap`].
+pub struct StableIterMut<'a, K, V> {
+    inner: IterMutInner<'a, K, V>,
+}
+
+enum IterMutInner<'a, K, V> {
+    Inline {
+        slots: std::slice::IterMut<'a, Slot<K, V>>,
+        remaining: usize,
+    },
+    Spilled(hash_map::IterMut<'a, K, V>),
+}
+
+impl<'a, K, V> Iterator for StableIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            IterMutInner::Inline { slots, remaining } => {
+                let item = slots.find_map(|slot| match slot {
+                    Slot::Occupied { key, value, .. } => Some((&*key, value)),
+                    Slot::Vacant { .. } => None,
+                })?;
+                *remaining -= 1;
+                Some(item)
+            }
+            IterMutInner::Spilled(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            IterMutInner::Inline { remaining, .. } => (*remaining, Some(*remaining)),
+            IterMutInner::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for StableIterMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for StableIterMut<'_, K, V> {}
+
+impl<K, V> Debug for StableIterMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StableIterMut").finish_non_exhaustive()
+    }
+}
+
+/// An owning iterator over the entries of a [`StableCompactMap`].
+///
+/// This `struct` is created by the [`into_iter`](IntoIterator::into_iter) method on
+/// [`StableCompactMap`] (provided by the [`IntoIterator`] trait).
+pub struct StableIntoIter<K, V, const N: usize> {
+    inner: IntoIterInner<K, V, N>,
+}
+
+enum IntoIterInner<K, V, const N: usize> {
+    Inline {
+        slots: std::array::IntoIter<Slot<K, V>, N>,
+        remaining: usize,
+    },
+    Spilled(hash_map::IntoIter<K, V>),
+}
+
+impl<K, V, const N: usize> Iterator for StableIntoIter<K, V, N> {
+    type Item = (K, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            IntoIterInner::Inline { slots, remaining } => {
+                let item = slots.find_map(|slot| match slot {
+                    Slot::Occupied { key, value, .. } => Some((key, value)),
+                    Slot::Vacant { .. } => None,
+                })?;
+                *remaining -= 1;
+                Some(item)
+            }
+            IntoIterInner::Spilled(iter) => iter.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            IntoIterInner::Inline { remaining, .. } => (*remaining, Some(*remaining)),
+            IntoIterInner::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<K, V, const N: usize> ExactSizeIterator for StableIntoIter<K, V, N> {}
+
+impl<K, V, const N: usize> FusedIterator for StableIntoIter<K, V, N> {}
+
+impl<K, V, const N: usize> Debug for StableIntoIter<K, V, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StableIntoIter").finish_non_exhaustive()
+    }
+}
@@ -1 +1,82 @@
+use crate::DEFAULT_MAX_INLINE_ENTRIES;
 
+/// Inline capacity used by `compact_map!` when no explicit `N` is given.
+#[doc(hidden)]
+pub const fn inline_capacity(keys: &[&str]) -> usize {
+    if keys.len() > DEFAULT_MAX_INLINE_ENTRIES {
+        keys.len()
+    } else {
+        DEFAULT_MAX_INLINE_ENTRIES
+    }
+}
+
+/// Returns `true` if any two of the stringified literal keys are identical.
+#[doc(hidden)]
+pub const fn has_duplicate_literals(keys: &[&str]) -> bool {
+    let mut i = 0;
+    while i < keys.len() {
+        let mut j = i + 1;
+        while j < keys.len() {
+            if str_eq(keys[i], keys[j]) {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Asserts a safety precondition of an `unsafe` block.
+///
+/// Only checked with the `checked` feature, otherwise it compiles to nothing.
+macro_rules! checked_assert {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "checked")]
+        assert!($($arg)*);
+    };
+}
+pub(crate) use checked_assert;
+
+/// [`std::hint::unreachable_unchecked`], which panics instead with the `checked` feature.
+///
+/// # Safety
+///
+/// Must never be reached unless the `checked` feature is enabled.
+#[inline(always)]
+pub(crate) unsafe fn unreachable_unchecked() -> ! {
+    #[cfg(feature = "checked")]
+    unreachable!("entered unreachable code in an `_unchecked` helper");
+    #[cfg(not(feature = "checked"))]
+    unsafe {
+        std::hint::unreachable_unchecked()
+    }
+}
+
+/// [`Option::unwrap_unchecked`], which panics instead with the `checked` feature.
+///
+/// # Safety
+///
+/// `opt` must be `Some` unless the `checked` feature is enabled.
+#[inline(always)]
+pub(crate) unsafe fn unwrap_unchecked<T>(opt: Option<T>) -> T {
+    match opt {
+        Some(t) => t,
+        None => unsafe { unreachable_unchecked() },
+    }
+}
@@ -0,0 +1,62 @@
+use compact_map::CompactMap;
+use std::rc::Rc;
+
+fn spilled_map(token: &Rc<()>) -> CompactMap<u32, (u32, Rc<()>), 4> {
+    let map: CompactMap<_, _, 4> = (0..10).map(|i| (i, (i, token.clone()))).collect();
+    assert!(map.spilled());
+    map
+}
+
+#[test]
+fn spilled_cursor_stopped_partway() {
+    let token = Rc::new(());
+    let mut map = spilled_map(&token);
+
+    let mut visited = Vec::new();
+    {
+        let mut cursor = map.cursor_mut();
+        for _ in 0..5 {
+            let mut entry = cursor.next().unwrap();
+            visited.push(*entry.key());
+            if entry.key() % 2 == 0 {
+                entry.remove();
+            } else {
+                entry.get_mut().0 += 100;
+            }
+        }
+    }
+
+    map.assert_invariants();
+    let removed = visited.iter().filter(|&&k| k % 2 == 0).count();
+    assert_eq!(map.len(), 10 - removed);
+    assert_eq!(Rc::strong_count(&token), 1 + map.len());
+    for (&k, (v, _)) in &map {
+        assert!(k % 2 == 1 || !visited.contains(&k));
+        let updated = visited.contains(&k);
+        assert_eq!(*v, if updated { k + 100 } else { k });
+    }
+}
+
+#[test]
+#[allow(clippy::forget_non_drop)] // forgetting the cursor must leave the map usable
+fn spilled_cursor_forgotten() {
+    let token = Rc::new(());
+    let mut map = spilled_map(&token);
+
+    let mut cursor = map.cursor_mut();
+    let removed = cursor.next().unwrap().remove_entry();
+    cursor.next().unwrap().get_mut().0 += 100;
+    cursor.next().unwrap();
+    std::mem::forget(cursor);
+
+    // visited in place, so forgetting the cursor loses nothing
+    map.assert_invariants();
+    assert_eq!(map.len(), 9);
+    assert_eq!(map.values().filter(|(v, _)| *v >= 100).count(), 1);
+    assert!(!map.contains_key(&removed.0));
+
+    // nothing is dropped twice, or leaked
+    drop(removed);
+    drop(map);
+    assert_eq!(Rc::strong_count(&token), 1);
+}
@@ -0,0 +1,47 @@
+//! Applies random operations to a `CompactMap` and a `HashMap`, and checks that they agree.
+
+#[path = "model/ops.rs"]
+mod ops;
+
+use compact_map::CompactMap;
+use ops::{apply, assert_same, Op};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+// a small key space, so that operations often hit existing keys
+fn key() -> impl Strategy<Value = u8> {
+    0..12u8
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (key(), any::<u8>()).prop_map(|(k, v)| Op::Insert(k, v)),
+        3 => key().prop_map(Op::Remove),
+        2 => (key(), any::<u8>()).prop_map(|(k, v)| Op::EntryOrInsert(k, v)),
+        2 => (key(), any::<u8>()).prop_map(|(k, v)| Op::EntryAndModify(k, v)),
+        2 => key().prop_map(Op::EntryRemove),
+        1 => Just(Op::PopAny),
+        1 => (0..16u8).prop_map(Op::SwapRemoveIndex),
+        // mostly small moduli, which remove a good share of the keys
+        1 => prop_oneof![4 => 0..4u8, 1 => any::<u8>()].prop_map(Op::Retain),
+        1 => Just(Op::Drain),
+        1 => vec((key(), any::<u8>()), 0..8).prop_map(Op::Extend),
+        1 => (0..16u8).prop_map(Op::Reserve),
+        1 => (0..8u8).prop_map(Op::ShrinkTo),
+        1 => Just(Op::ShrinkToFit),
+        1 => Just(Op::Spill),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn matches_hash_map(ops in vec(op(), 0..64)) {
+        let mut map = CompactMap::new();
+        let mut model = HashMap::new();
+        for op in ops {
+            apply(&mut map, &mut model, op);
+            assert_same(&map, &model);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! The operations that the model tests apply to a `CompactMap` and a `HashMap`.
+//!
+//! Shared by `tests/model.rs` and the `model` fuzz target, which generate them with
+//! proptest and `arbitrary` respectively.
+
+use compact_map::{CompactMap, Entry};
+use std::collections::HashMap;
+
+// only the fuzz target, which isn't built as a test, depends on `arbitrary`
+#[cfg_attr(not(test), derive(arbitrary::Arbitrary))]
+#[derive(Clone, Debug)]
+pub enum Op {
+    Insert(u8, u8),
+    Remove(u8),
+    EntryOrInsert(u8, u8),
+    EntryAndModify(u8, u8),
+    EntryRemove(u8),
+    PopAny,
+    SwapRemoveIndex(u8),
+    /// Keeps the keys that are not multiples of the value, or of 1 if it is 0.
+    Retain(u8),
+    Drain,
+    Extend(Vec<(u8, u8)>),
+    Reserve(u8),
+    ShrinkTo(u8),
+    ShrinkToFit,
+    Spill,
+}
+
+pub fn apply(map: &mut CompactMap<u8, u8, 4>, model: &mut HashMap<u8, u8>, op: Op) {
+    match op {
+        Op::Insert(k, v) => assert_eq!(map.insert(k, v), model.insert(k, v)),
+        Op::Remove(k) => assert_eq!(map.remove(&k), model.remove(&k)),
+        Op::EntryOrInsert(k, v) => {
+            assert_eq!(map.entry(k).or_insert(v), model.entry(k).or_insert(v));
+        }
+        Op::EntryAndModify(k, v) => {
+            map.entry(k)
+                .and_modify(|x| *x = x.wrapping_add(v))
+                .or_insert(v);
+            model
+                .entry(k)
+                .and_modify(|x| *x = x.wrapping_add(v))
+                .or_insert(v);
+        }
+        Op::EntryRemove(k) => {
+            let removed = match map.entry(k) {
+                Entry::Occupied(entry) => Some(entry.remove_entry()),
+                Entry::Vacant(_) => None,
+            };
+            assert_eq!(removed, model.remove_entry(&k));
+        }
+        Op::PopAny => match map.pop_any() {
+            Some((k, v)) => assert_eq!(model.remove(&k), Some(v)),
+            None => assert!(model.is_empty()),
+        },
+        Op::SwapRemoveIndex(index) => match map.swap_remove_index(index.into()) {
+            Some((k, v)) => assert_eq!(model.remove(&k), Some(v)),
+            None => assert!(usize::from(index) >= model.len()),
+        },
+        Op::Retain(m) => {
+            let m = m.max(1);
+            map.retain(|k, _| k % m != 0);
+            model.retain(|k, _| k % m != 0);
+        }
+        Op::Drain => {
+            let drained: HashMap<_, _> = map.drain().collect();
+            assert_eq!(drained, std::mem::take(model));
+        }
+        Op::Extend(entries) => {
+            map.extend(entries.iter().copied());
+            model.extend(entries);
+        }
+        Op::Reserve(additional) => map.reserve(additional.into()),
+        Op::ShrinkTo(min_capacity) => map.shrink_to(min_capacity.into()),
+        Op::ShrinkToFit => map.shrink_to_fit(),
+        Op::Spill => map.spill(),
+    }
+}
+
+pub fn assert_same(map: &CompactMap<u8, u8, 4>, model: &HashMap<u8, u8>) {
+    map.assert_invariants();
+    assert_eq!(map.len(), model.len());
+    assert_eq!(map.iter().count(), model.len());
+    for (k, v) in model {
+        assert_eq!(map.get(k), Some(v));
+    }
+    let contents: HashMap<_, _> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(&contents, model);
+}
@@ -0,0 +1,228 @@
+use compact_map::{CompactMap, StableCompactMap};
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+thread_local! {
+    /// The id whose `Hash` panics, and how many times it hashes fine before that.
+    static ARMED: Cell<Option<(u32, u32)>> = const { Cell::new(None) };
+}
+
+/// A key whose `Hash` panics for the armed id.
+#[derive(Debug, PartialEq, Eq)]
+struct Key {
+    id: u32,
+    // counts live values, to catch both leaks and double drops
+    token: Rc<()>,
+}
+
+impl Key {
+    fn new(id: u32, token: &Rc<()>) -> Self {
+        Self {
+            id,
+            token: Rc::clone(token),
+        }
+    }
+}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Some((id, spare)) = ARMED.with(Cell::get) {
+            if id == self.id {
+                if spare == 0 {
+                    panic!("hash of key {id}");
+                }
+                ARMED.with(|armed| armed.set(Some((id, spare - 1))));
+            }
+        }
+        self.id.hash(state);
+    }
+}
+
+fn full_map(token: &Rc<()>) -> CompactMap<Key, u32, 4> {
+    let mut map = CompactMap::new();
+    for id in 0..4 {
+        map.insert(Key::new(id, token), id * 10);
+    }
+    map
+}
+
+fn assert_intact(map: &CompactMap<Key, u32, 4>) {
+    assert!(!map.spilled());
+    assert_eq!(map.len(), 4);
+    map.assert_invariants();
+    let mut ids: Vec<_> = map.iter().map(|(k, v)| (k.id, *v)).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, [(0, 0), (1, 10), (2, 20), (3, 30)]);
+}
+
+/// Runs `f` with the `Hash` of key `id` panicking after `spare` successful calls.
+fn with_panicking_hash<R>(id: u32, spare: u32, f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    ARMED.with(|armed| armed.set(Some((id, spare))));
+    let result = catch_unwind(AssertUnwindSafe(f));
+    ARMED.with(|armed| armed.set(None));
+    result
+}
+
+fn with_armed_hash<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    with_panicking_hash(3, 0, f)
+}
+
+#[test]
+fn spill_keeps_entries_when_hash_panics() {
+    let token = Rc::new(());
+    let mut map = full_map(&token);
+
+    assert!(with_armed_hash(|| map.spill()).is_err());
+    assert_intact(&map);
+    assert_eq!(Rc::strong_count(&token), 5);
+
+    map.spill();
+    assert!(map.spilled());
+    assert_eq!(map.len(), 4);
+}
+
+#[test]
+fn insert_keeps_entries_when_spill_panics() {
+    let token = Rc::new(());
+    let mut map = full_map(&token);
+
+    let new_key = Key::new(4, &token);
+    assert!(with_armed_hash(|| map.insert(new_key, 40)).is_err());
+    assert_intact(&map);
+    // the key that couldn't be inserted was dropped
+    assert_eq!(Rc::strong_count(&token), 5);
+}
+
+#[test]
+fn entry_insert_keeps_entries_when_spill_panics() {
+    let token = Rc::new(());
+    let mut map = full_map(&token);
+
+    let new_key = Key::new(4, &token);
+    assert!(with_armed_hash(|| *map.entry(new_key).or_insert(40) += 1).is_err());
+    assert_intact(&map);
+    assert_eq!(Rc::strong_count(&token), 5);
+}
+
+#[test]
+fn reserve_keeps_entries_when_hash_panics() {
+    let token = Rc::new(());
+    let mut map = full_map(&token);
+
+    assert!(with_armed_hash(|| map.reserve(16)).is_err());
+    assert_intact(&map);
+    assert!(with_armed_hash(|| map.try_reserve(16)).is_err());
+    assert_intact(&map);
+
+    drop(map);
+    assert_eq!(Rc::strong_count(&token), 1);
+}
+
+#[test]
+fn spill_moves_entries_back_when_hash_panics_midway() {
+    let token = Rc::new(());
+    let mut map = full_map(&token);
+
+    // Key 0 is the last one moved, so its hash panics with the other entries already
+    // in the `HashMap`.
+    assert!(with_panicking_hash(0, 0, || map.spill()).is_err());
+    assert_intact(&map);
+    // no entry is lost or dropped
+    assert_eq!(Rc::strong_count(&token), 5);
+
+    map.spill();
+    assert_eq!(map.len(), 4);
+    drop(map);
+    assert_eq!(Rc::strong_count(&token), 1);
+}
+
+#[test]
+fn pop_any_keeps_entries_when_shrinking_panics() {
+    let token = Rc::new(());
+    let mut map = full_map(&token);
+    for id in 4..64 {
+        map.insert(Key::new(id, &token), id * 10);
+    }
+    for id in 4..64 {
+        map.remove(&Key::new(id, &token));
+    }
+    assert!(map.spilled());
+
+    // the table is mostly empty, so it is shrunk first, which hashes key 3
+    assert!(with_armed_hash(|| map.pop_any()).is_err());
+    assert!(map.spilled());
+    assert_eq!(map.len(), 4);
+    map.assert_invariants();
+    assert_eq!(Rc::strong_count(&token), 5);
+
+    let mut popped = Vec::new();
+    while let Some((k, v)) = map.pop_any() {
+        popped.push((k.id, v));
+    }
+    popped.sort_unstable();
+    assert_eq!(popped, [(0, 0), (1, 10), (2, 20), (3, 30)]);
+    assert_eq!(Rc::strong_count(&token), 1);
+}
+
+#[test]
+fn split_off_if_keeps_entries_when_pred_panics() {
+    let token = Rc::new(());
+    let mut map = full_map(&token);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        // key 0 is moved, and key 3 takes its place
+        map.split_off_if::<_, 2>(|k, _| k.id == 0 || panic!("pred of key {}", k.id))
+    }));
+    assert!(result.is_err());
+    assert_intact(&map);
+    assert_eq!(Rc::strong_count(&token), 5);
+
+    for id in 4..8 {
+        map.insert(Key::new(id, &token), id * 10);
+    }
+    // five entries are moved, which spills the other map, before the sixth call panics
+    let mut calls = 0;
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        map.split_off_if::<_, 2>(|_, _| {
+            calls += 1;
+            calls < 6 || panic!("pred call {calls}")
+        })
+    }));
+    assert!(result.is_err());
+    assert!(map.spilled());
+    assert_eq!(map.len(), 8);
+    map.assert_invariants();
+    assert_eq!(Rc::strong_count(&token), 9);
+}
+
+#[test]
+fn stable_spill_keeps_entries_and_free_list_when_hash_panics_midway() {
+    let token = Rc::new(());
+    let mut map: StableCompactMap<Key, u32, 4> = StableCompactMap::new();
+    for id in 0..4 {
+        map.insert(Key::new(id, &token), id * 10);
+    }
+
+    // Key 2 panics once keys 0 and 1 have moved.
+    let result = with_panicking_hash(2, 0, || map.insert(Key::new(4, &token), 40));
+    assert!(result.is_err());
+    assert!(!map.spilled());
+    assert_eq!(map.len(), 4);
+    let mut ids: Vec<_> = map.iter().map(|(k, v)| (k.id, *v)).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, [(0, 0), (1, 10), (2, 20), (3, 30)]);
+    for (id, value) in ids {
+        let handle = map.handle_of(&Key::new(id, &token)).unwrap();
+        assert_eq!(map.get_by_handle(handle), Some(&value));
+    }
+    // only the entry being inserted is lost
+    assert_eq!(Rc::strong_count(&token), 5);
+
+    // the free list is intact, so the map is full, and spills on the next insert
+    map.insert(Key::new(5, &token), 50);
+    assert!(map.spilled());
+    assert_eq!(map.len(), 5);
+    drop(map);
+    assert_eq!(Rc::strong_count(&token), 1);
+}